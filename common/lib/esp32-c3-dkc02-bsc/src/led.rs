@@ -1,30 +1,22 @@
-use std::ptr::{null, null_mut};
+use std::{ptr::null_mut, slice};
 
+use esp_idf_hal::{
+    gpio::{Gpio2, OutputPin, Unknown},
+    rmt::{HwChannel, CHANNEL0},
+};
 use esp_idf_sys::{
-    c_types::c_void, esp, rmt_config, rmt_config_t, rmt_config_t__bindgen_ty_1, rmt_driver_install,
-    rmt_get_counter_clock, rmt_item32_t, rmt_item32_t__bindgen_ty_1,
-    rmt_item32_t__bindgen_ty_1__bindgen_ty_1, rmt_mode_t_RMT_MODE_TX, rmt_translator_init,
-    rmt_tx_config_t, rmt_wait_tx_done, rmt_write_sample, size_t, u_int8_t,
+    c_types::c_void, esp, rmt_channel_t, rmt_config, rmt_config_t, rmt_config_t__bindgen_ty_1,
+    rmt_driver_install, rmt_driver_uninstall, rmt_get_counter_clock, rmt_item32_t,
+    rmt_mode_t_RMT_MODE_TX, rmt_translator_get_context, rmt_translator_init,
+    rmt_translator_set_context, rmt_tx_config_t, rmt_wait_tx_done, rmt_write_sample, size_t,
 };
 pub use rgb::RGB8;
-
-const WS2812_T0H_NS: u32 = 350;
-const WS2812_T0L_NS: u32 = 1000;
-const WS2812_T1H_NS: u32 = 1000;
-const WS2812_T1L_NS: u32 = 350;
-
-#[derive(Debug, Default, Clone, Copy)]
-struct Ws2812Config {
-    t0h_ticks: u32,
-    t0l_ticks: u32,
-    t1h_ticks: u32,
-    t1l_ticks: u32,
-}
+use smart_leds_trait::SmartLedsWrite;
+use ws2812::Timing;
 
 const FREERTOS_HZ: u32 = 1000;
 
-static mut WS_CONFIG: Option<Ws2812Config> = None;
-
+/// Translator of every channel, its `Timing` is the channel's context
 unsafe extern "C" fn ws2812_to_rmt(
     src: *const c_void,
     dest: *mut rmt_item32_t,
@@ -33,65 +25,68 @@ unsafe extern "C" fn ws2812_to_rmt(
     translated_size: *mut size_t,
     item_num: *mut size_t,
 ) {
-    if src == null() || dest == null_mut() {
+    let mut context = null_mut();
+    if src.is_null()
+        || dest.is_null()
+        || esp!(rmt_translator_get_context(item_num, &mut context)).is_err()
+        || context.is_null()
+    {
         *translated_size = 0;
         *item_num = 0;
         return;
     }
 
-    let config = WS_CONFIG.unwrap();
-    let mut bit0: rmt_item32_t__bindgen_ty_1__bindgen_ty_1 = Default::default();
-    bit0.set_duration0(config.t0h_ticks);
-    bit0.set_level0(1);
-    bit0.set_duration1(config.t0l_ticks);
-    bit0.set_level1(0);
-
-    let bit0 = rmt_item32_t {
-        __bindgen_anon_1: rmt_item32_t__bindgen_ty_1 {
-            __bindgen_anon_1: bit0,
-        },
-    };
-
-    let mut bit1: rmt_item32_t__bindgen_ty_1__bindgen_ty_1 = Default::default();
-    bit1.set_duration0(config.t1h_ticks);
-    bit1.set_level0(1);
-    bit1.set_duration1(config.t1l_ticks);
-    bit1.set_level1(0);
-
-    let bit1 = rmt_item32_t {
-        __bindgen_anon_1: rmt_item32_t__bindgen_ty_1 {
-            __bindgen_anon_1: bit1,
-        },
-    };
-
-    let mut size: size_t = 0;
-    let mut num = 0;
-
-    let mut psrc = src as *const u_int8_t;
-    let mut pdest: *mut rmt_item32_t = dest as _;
-
-    while size < src_size && num < wanted_num {
-        for i in 0..8 {
-            if *psrc & (1 << (7 - i)) != 0 {
-                *pdest = bit1;
-            } else {
-                *pdest = bit0;
-            }
-            num += 1;
-            pdest = pdest.add(1);
-        }
-        size += 1;
-        psrc = psrc.add(1);
-    }
-    *translated_size = size;
-    *item_num = num;
+    // SAFETY: `WS2812RMT` keeps the context alive while the driver is
+    // installed, and `rmt_item32_t` is a union over a `u32`
+    let timing = &*(context as *const Timing);
+    let src = slice::from_raw_parts(src as *const u8, src_size as usize);
+    let dest = slice::from_raw_parts_mut(dest as *mut u32, wanted_num as usize);
+
+    let (size, num) = ws2812::encode(timing, src, dest);
+    *translated_size = size as _;
+    *item_num = num as _;
 }
 
-pub struct WS2812RMT {
-    config: rmt_config_t,
+/// A WS2812 LED, or a strip of them, driven by one RMT channel
+///
+/// Colors go into a frame buffer, `pixels_mut`, and `flush` sends the whole
+/// frame in one RMT transaction. Owns the pin and the RMT channel it drives,
+/// so no one else can use them while the LED is in use. `release` hands them
+/// back. Every LED keeps its own timing, so several can run side by side.
+pub struct WS2812RMT<P = Gpio2<Unknown>, C = CHANNEL0> {
+    /// `None` once released
+    peripherals: Option<(P, C)>,
+    /// `C::channel()`, for `Drop`, which cannot require `C: HwChannel`
+    channel: rmt_channel_t,
+    /// the translator's context, boxed so it does not move
+    timing: Box<Timing>,
+    pixels: Vec<RGB8>,
+    /// `pixels` as sent, GRB
+    frame: Vec<u8>,
 }
+
 impl WS2812RMT {
+    /// The board's LED, on GPIO2 and RMT channel 0
+    ///
+    /// Conjures up the peripherals, so do not also `take()` them elsewhere
+    /// for GPIO2 or RMT channel 0. Use `with_pin` if you do.
     pub fn new() -> anyhow::Result<Self> {
+        // SAFETY: the previous hard-coded behaviour, documented above
+        let (pin, channel) = unsafe { (Gpio2::<Unknown>::new(), CHANNEL0::new()) };
+        Self::with_pin(pin, channel)
+    }
+}
+
+impl<P: OutputPin, C: HwChannel> WS2812RMT<P, C> {
+    /// A single LED on `pin`, driven by RMT `channel`
+    ///
+    /// On the ESP32-C3 only channels 0 and 1 can transmit.
+    pub fn with_pin(pin: P, channel: C) -> anyhow::Result<Self> {
+        Self::strip(pin, channel, 1)
+    }
+
+    /// A strip of `len` LEDs on `pin`, driven by RMT `channel`, all off
+    pub fn strip(pin: P, channel: C, len: usize) -> anyhow::Result<Self> {
         let rmt_tx_config = rmt_tx_config_t {
             carrier_freq_hz: 38000,
             carrier_level: 1,
@@ -105,8 +100,8 @@ impl WS2812RMT {
 
         let config = rmt_config_t {
             rmt_mode: rmt_mode_t_RMT_MODE_TX,
-            channel: 0,
-            gpio_num: 2,
+            channel: C::channel(),
+            gpio_num: pin.pin(),
             clk_div: 2,
             mem_block_num: 1,
             flags: 0,
@@ -118,39 +113,108 @@ impl WS2812RMT {
         unsafe {
             esp!(rmt_config(&config))?;
             esp!(rmt_driver_install(config.channel, 0, 0))?;
+        }
+        // from here on, dropping `led` uninstalls the driver
+        let mut led = Self {
+            peripherals: Some((pin, channel)),
+            channel: config.channel,
+            timing: Box::new(Timing::new(0)),
+            pixels: vec![RGB8::default(); len],
+            frame: Vec::with_capacity(3 * len),
+        };
+
+        unsafe {
             let mut rmt_clock = 0u32;
             esp!(rmt_get_counter_clock(config.channel, &mut rmt_clock))?;
+            *led.timing = Timing::new(rmt_clock);
 
-            let ratio = rmt_clock as f64 / 1e9;
+            esp!(rmt_translator_init(config.channel, Some(ws2812_to_rmt)))?;
+            esp!(rmt_translator_set_context(
+                config.channel,
+                &*led.timing as *const Timing as *mut c_void,
+            ))?;
+        }
 
-            WS_CONFIG = Some(Ws2812Config {
-                t0h_ticks: (ratio * WS2812_T0H_NS as f64) as _,
-                t0l_ticks: (ratio * WS2812_T0L_NS as f64) as _,
-                t1h_ticks: (ratio * WS2812_T1H_NS as f64) as _,
-                t1l_ticks: (ratio * WS2812_T1L_NS as f64) as _,
-            });
+        Ok(led)
+    }
 
-            esp!(rmt_translator_init(config.channel, Some(ws2812_to_rmt)))?;
+    /// Uninstalls the RMT driver and gives back the pin and channel
+    pub fn release(mut self) -> anyhow::Result<(P, C)> {
+        unsafe {
+            esp!(rmt_driver_uninstall(self.channel))?;
         }
+        Ok(self.peripherals.take().expect("only released once"))
+    }
 
-        Ok(Self { config })
+    /// The frame buffer, sent by the next `flush`
+    pub fn pixels(&self) -> &[RGB8] {
+        &self.pixels
     }
 
+    pub fn pixels_mut(&mut self) -> &mut [RGB8] {
+        &mut self.pixels
+    }
+
+    /// Sets every LED to `color` and sends the frame
     pub fn set_pixel(&mut self, color: RGB8) -> anyhow::Result<()> {
-        let timeout_ms = 1;
+        self.pixels.fill(color);
+        self.flush()
+    }
+
+    /// Sends the frame buffer and waits until the strip got it
+    pub fn flush(&mut self) -> anyhow::Result<()> {
+        self.frame.clear();
+        for color in &self.pixels {
+            // WS2812 expects GRB, not RGB
+            self.frame.extend_from_slice(&[color.g, color.r, color.b]);
+        }
+        // a pixel takes 30 µs, give each started millisecond one more
+        let timeout_ms = 1 + self.pixels.len() as u32 * 30 / 1000;
         unsafe {
             esp!(rmt_write_sample(
-                self.config.channel,
-                &[color.g, color.r, color.b] as *const u8, // WS2812 expects GRB, not RGB
-                3,
+                self.channel,
+                self.frame.as_ptr(),
+                self.frame.len() as _,
                 true,
             ))?;
             esp!(rmt_wait_tx_done(
-                self.config.channel,
-                (timeout_ms as u32 * FREERTOS_HZ) / 1000,
+                self.channel,
+                (timeout_ms * FREERTOS_HZ) / 1000,
             ))?;
         }
 
         Ok(())
     }
 }
+
+impl<P, C> Drop for WS2812RMT<P, C> {
+    fn drop(&mut self) {
+        if self.peripherals.is_some() {
+            // the translator must not see `timing` after this
+            unsafe {
+                rmt_driver_uninstall(self.channel);
+            }
+        }
+    }
+}
+
+impl<P: OutputPin, C: HwChannel> SmartLedsWrite for WS2812RMT<P, C> {
+    type Error = anyhow::Error;
+    type Color = RGB8;
+
+    /// Fills the frame with `iterator`'s colors and sends it
+    ///
+    /// Colors beyond the strip's length are ignored, LEDs the iterator does
+    /// not reach are turned off.
+    fn write<T, I>(&mut self, iterator: T) -> anyhow::Result<()>
+    where
+        T: Iterator<Item = I>,
+        I: Into<RGB8>,
+    {
+        let mut colors = iterator.map(Into::into);
+        for pixel in self.pixels_mut() {
+            *pixel = colors.next().unwrap_or_default();
+        }
+        self.flush()
+    }
+}
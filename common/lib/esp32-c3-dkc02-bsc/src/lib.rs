@@ -1,3 +1,4 @@
 pub mod led;
+pub mod nvs;
 pub mod temp_sensor;
 pub mod wifi;
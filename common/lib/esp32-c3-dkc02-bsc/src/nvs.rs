@@ -0,0 +1,77 @@
+// Small values that survive reboots, kept in the default NVS partition.
+// The partition has to be initialized first, which `wifi::wifi()` does.
+
+use std::ffi::CString;
+
+use esp_idf_sys::{
+    esp, esp_err_t, nvs_close, nvs_commit, nvs_get_u32, nvs_get_u64, nvs_handle_t, nvs_open,
+    nvs_open_mode_t_NVS_READWRITE, nvs_set_u32, nvs_set_u64, ESP_ERR_NVS_NOT_FOUND,
+};
+
+pub struct Nvs {
+    handle: nvs_handle_t,
+}
+
+impl Nvs {
+    /// Opens (and creates, if needed) `namespace` for reading and writing
+    pub fn open(namespace: &str) -> anyhow::Result<Self> {
+        let namespace = CString::new(namespace)?;
+        let mut handle = 0;
+        esp!(unsafe { nvs_open(namespace.as_ptr(), nvs_open_mode_t_NVS_READWRITE, &mut handle) })?;
+        Ok(Self { handle })
+    }
+
+    pub fn get_u32(&self, key: &str) -> anyhow::Result<Option<u32>> {
+        let key = CString::new(key)?;
+        let mut value = 0;
+        let err = unsafe { nvs_get_u32(self.handle, key.as_ptr(), &mut value) };
+        if err == ESP_ERR_NVS_NOT_FOUND as esp_err_t {
+            return Ok(None);
+        }
+        esp!(err)?;
+        Ok(Some(value))
+    }
+
+    /// Stores and commits `value`
+    pub fn set_u32(&mut self, key: &str, value: u32) -> anyhow::Result<()> {
+        let key = CString::new(key)?;
+        esp!(unsafe { nvs_set_u32(self.handle, key.as_ptr(), value) })?;
+        esp!(unsafe { nvs_commit(self.handle) })?;
+        Ok(())
+    }
+
+    pub fn get_u64(&self, key: &str) -> anyhow::Result<Option<u64>> {
+        let key = CString::new(key)?;
+        let mut value = 0;
+        let err = unsafe { nvs_get_u64(self.handle, key.as_ptr(), &mut value) };
+        if err == ESP_ERR_NVS_NOT_FOUND as esp_err_t {
+            return Ok(None);
+        }
+        esp!(err)?;
+        Ok(Some(value))
+    }
+
+    /// Stores and commits `value`
+    pub fn set_u64(&mut self, key: &str, value: u64) -> anyhow::Result<()> {
+        let key = CString::new(key)?;
+        esp!(unsafe { nvs_set_u64(self.handle, key.as_ptr(), value) })?;
+        esp!(unsafe { nvs_commit(self.handle) })?;
+        Ok(())
+    }
+
+    /// Increments the counter stored under `key` and returns its new value
+    ///
+    /// The new value is committed before it is returned, so every call
+    /// returns a different value, even across reboots.
+    pub fn increment_u32(&mut self, key: &str) -> anyhow::Result<u32> {
+        let value = self.get_u32(key)?.unwrap_or(0).wrapping_add(1);
+        self.set_u32(key, value)?;
+        Ok(value)
+    }
+}
+
+impl Drop for Nvs {
+    fn drop(&mut self) {
+        unsafe { nvs_close(self.handle) };
+    }
+}
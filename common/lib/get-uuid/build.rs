@@ -1,24 +1,63 @@
-use std::{fs::File, io::Write};
+use std::{
+    collections::HashMap,
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
 
+use anyhow::{bail, Context};
 use uuid::Uuid;
 
+#[path = "build/identity.rs"]
+mod identity;
+#[path = "src/namespace.rs"]
+mod namespace;
+
 fn main() -> anyhow::Result<()> {
-    if let Ok(_already_exists) = File::open("uuid.toml") {
-        return Ok(());
+    let mut vars = HashMap::new();
+    for name in identity::VARS {
+        if let Some(value) = env_var(name)? {
+            vars.insert(name, value);
+        }
     }
+    let env = |name: &str| vars.get(name).cloned();
+
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR")?;
+    let uuid_file = identity::uuid_file(Path::new(&manifest_dir), &env);
 
-    let mut uuid_file = File::create("uuid.toml")?;
-    uuid_file.write_all("[get-uuid]\n".as_bytes())?;
-    let uuid_val = Uuid::new_v4().to_string();
-    uuid_file.write_fmt(format_args!("uuid = \"{}\"\n", uuid_val))?;
+    let mut text = match fs::read_to_string(&uuid_file) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e).with_context(|| format!("could not read {}", uuid_file.display())),
+    };
+    if text.is_none() && !identity::from_env(&env) {
+        let new_file = identity::new_file(Uuid::new_v4());
+        fs::write(&uuid_file, &new_file)
+            .with_context(|| format!("could not create {}", uuid_file.display()))?;
+        text = Some(new_file);
+    }
+    let read_file = text.as_ref().map(|_| uuid_file.as_path());
+    for directive in identity::rerun_directives(read_file) {
+        println!("{}", directive);
+    }
 
-    let package_root = env!("CARGO_MANIFEST_DIR");
-    let uuid_rs = format!("{}/_uuid.rs", package_root);
-    let mut uuid_file = File::create(uuid_rs)?;
-    uuid_file.write_fmt(format_args!(
-        "const UUID: &'static str = \"{}\";\n",
-        uuid_val
-    ))?;
+    let identity = identity::resolve(text.as_deref(), &env)
+        .with_context(|| format!("no UUID from {}", uuid_file.display()))?;
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+    fs::write(
+        out_dir.join(identity::SOURCE_FILE),
+        identity::source(&identity),
+    )?;
 
     Ok(())
 }
+
+/// An environment variable, `None` if unset or empty
+fn env_var(name: &str) -> anyhow::Result<Option<String>> {
+    match env::var(name) {
+        Ok(value) if value.is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => bail!("{} is not valid UTF-8", name),
+    }
+}
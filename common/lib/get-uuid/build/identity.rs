@@ -0,0 +1,165 @@
+// How `build.rs` finds the build-time UUID. Kept free of I/O so
+// `tests/build_script.rs` can check it without running cargo.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context};
+use uuid::Uuid;
+
+use crate::namespace;
+
+/// Path of `uuid.toml`, relative paths start at the package root
+pub const FILE_VAR: &str = "GET_UUID_FILE";
+/// An explicit UUID, wins over everything else
+pub const UUID_VAR: &str = "GET_UUID";
+/// A device name to derive the UUID from, wins over `uuid.toml`
+pub const DEVICE_NAME_VAR: &str = "GET_UUID_DEVICE_NAME";
+/// The namespace for `DEVICE_NAME_VAR` and `device_name`
+pub const NAMESPACE_VAR: &str = "GET_UUID_NAMESPACE";
+
+pub const VARS: [&str; 4] = [FILE_VAR, UUID_VAR, DEVICE_NAME_VAR, NAMESPACE_VAR];
+
+/// Name of the generated file in `OUT_DIR`
+pub const SOURCE_FILE: &str = "_uuid.rs";
+
+/// Looks up an environment variable, `None` if unset or empty
+pub type Env<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Identity {
+    pub uuid: Uuid,
+    /// boards go by `uuid` instead of the UUID derived from their MAC
+    pub is_override: bool,
+}
+
+/// Where `uuid.toml` is, no matter which directory cargo runs in
+pub fn uuid_file(manifest_dir: &Path, env: Env) -> PathBuf {
+    match env(FILE_VAR) {
+        Some(file) => manifest_dir.join(file),
+        None => manifest_dir.join("uuid.toml"),
+    }
+}
+
+/// What makes cargo run `build.rs` again
+///
+/// `uuid_file` is `None` if there is no such file. Cargo would rerun on every
+/// build when watching a missing file, and the environment decides the UUID
+/// then anyway.
+pub fn rerun_directives(uuid_file: Option<&Path>) -> Vec<String> {
+    let mut directives: Vec<_> = uuid_file
+        .map(|file| format!("cargo:rerun-if-changed={}", file.display()))
+        .into_iter()
+        .collect();
+    for var in VARS {
+        directives.push(format!("cargo:rerun-if-env-changed={}", var));
+    }
+    directives
+}
+
+/// Whether the environment alone decides the UUID, so `uuid.toml` may be missing
+pub fn from_env(env: Env) -> bool {
+    env(UUID_VAR).is_some() || env(DEVICE_NAME_VAR).is_some()
+}
+
+/// A fresh `uuid.toml` with `uuid`
+pub fn new_file(uuid: Uuid) -> String {
+    format!(
+        "[get-uuid]\n\
+         uuid = \"{}\"\n\
+         # or, the same on every machine: a UUIDv5 of a name in a namespace\n\
+         # device_name = \"desk-board\"\n\
+         # namespace = \"{}\"\n\
+         # boards go by this UUID instead of the one derived from their MAC\n\
+         override = false\n",
+        uuid,
+        Uuid::from_bytes(namespace::NAMESPACE)
+    )
+}
+
+/// The UUID from the first of `GET_UUID`, `GET_UUID_DEVICE_NAME`, `uuid` and
+/// `device_name` in `file` that is set
+pub fn resolve(file: Option<&str>, env: Env) -> anyhow::Result<Identity> {
+    let config: toml::Value = match file {
+        Some(text) => text.parse().context("uuid.toml is not valid TOML")?,
+        None => toml::Value::Table(Default::default()),
+    };
+    let table = config.get("get-uuid");
+    let key = |key: &str| table.and_then(|table| table.get(key));
+    let text = |name: &str| -> anyhow::Result<Option<String>> {
+        key(name)
+            .map(|value| {
+                value
+                    .as_str()
+                    .map(str::to_owned)
+                    .ok_or_else(|| anyhow!("`{}` in uuid.toml must be a string", name))
+            })
+            .transpose()
+    };
+
+    let uuid = if let Some(uuid) = env(UUID_VAR) {
+        parse(&uuid).with_context(|| format!("{} is not a valid UUID", UUID_VAR))?
+    } else if let Some(name) = env(DEVICE_NAME_VAR) {
+        derive(&name, text("namespace")?, env)?
+    } else if let Some(uuid) = text("uuid")? {
+        parse(&uuid).context("`uuid` in uuid.toml is not a valid UUID")?
+    } else if let Some(name) = text("device_name")? {
+        derive(&name, text("namespace")?, env)?
+    } else {
+        bail!(
+            "uuid.toml needs a [get-uuid] table with `uuid` or `device_name`, \
+             or set {} or {}",
+            UUID_VAR,
+            DEVICE_NAME_VAR
+        );
+    };
+
+    // older files have no `override`, their boards switch to derived UUIDs
+    let is_override = key("override")
+        .map(|value| {
+            value
+                .as_bool()
+                .ok_or_else(|| anyhow!("`override` in uuid.toml must be true or false"))
+        })
+        .transpose()?
+        .unwrap_or(false);
+
+    Ok(Identity { uuid, is_override })
+}
+
+/// Contents of `_uuid.rs`
+pub fn source(identity: &Identity) -> String {
+    format!(
+        "const UUID: &str = \"{}\";\nconst UUID_BYTES: [u8; 16] = {:?};\nconst OVERRIDE: bool = {};\n",
+        identity.uuid,
+        identity.uuid.as_bytes(),
+        identity.is_override
+    )
+}
+
+/// Accepts the hyphenated form only, e.g. `371c0d6c-1712-41c3-95bb-e84bf5c0ca3b`
+fn parse(text: &str) -> anyhow::Result<Uuid> {
+    let uuid = Uuid::parse_str(text).map_err(|e| anyhow!("{:?}: {}", text, e))?;
+    if text.len() != 36 {
+        bail!("{:?}: expected the hyphenated form", text);
+    }
+    Ok(uuid)
+}
+
+/// UUIDv5 of `name` in `namespace`, `GET_UUID_NAMESPACE` or the default one
+fn derive(name: &str, namespace: Option<String>, env: Env) -> anyhow::Result<Uuid> {
+    if name.is_empty() {
+        bail!("the device name must not be empty");
+    }
+    let namespace = match env(NAMESPACE_VAR) {
+        Some(namespace) => {
+            parse(&namespace).with_context(|| format!("{} is not a valid UUID", NAMESPACE_VAR))?
+        }
+        None => match namespace {
+            Some(namespace) => {
+                parse(&namespace).context("`namespace` in uuid.toml is not a valid UUID")?
+            }
+            None => Uuid::from_bytes(namespace::NAMESPACE),
+        },
+    };
+    Ok(Uuid::new_v5(&namespace, name.as_bytes()))
+}
@@ -0,0 +1,58 @@
+// Short names for UUIDs, to read out loud or put in a log line
+
+use uuid::Uuid;
+
+/// Crockford's base32: no `i`, `l`, `o` or `u`, so nothing looks or sounds alike
+const ALPHABET: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+/// Digits of an alias, 5 bits each
+pub const ALIAS_DIGITS: usize = 8;
+
+/// The first 40 bits of `uuid` in base32, e.g. `6we0-tv0q` for `371c0d6c-17...`
+///
+/// Two UUIDs share an alias with a chance of one in a trillion, so within a
+/// fleet an alias is as good as the UUID.
+pub fn alias(uuid: &Uuid) -> String {
+    let bits = uuid.as_bytes()[..5]
+        .iter()
+        .fold(0u64, |bits, byte| bits << 8 | *byte as u64);
+    let mut alias = String::with_capacity(ALIAS_DIGITS + 1);
+    for i in 0..ALIAS_DIGITS {
+        if i == ALIAS_DIGITS / 2 {
+            alias.push('-');
+        }
+        let digit = (bits >> (5 * (ALIAS_DIGITS - 1 - i))) & 0x1f;
+        alias.push(ALPHABET[digit as usize] as char);
+    }
+    alias
+}
+
+/// The UUID of `known` that goes by `alias`
+///
+/// Aliases are read back the way people type them: in any case, with or
+/// without the `-`, and with `i`/`l` for `1` and `o` for `0`.
+pub fn from_alias(alias: &str, known: impl IntoIterator<Item = Uuid>) -> Option<Uuid> {
+    let wanted = normalize(alias)?;
+    known
+        .into_iter()
+        .find(|uuid| self::alias(uuid).replace('-', "") == wanted)
+}
+
+/// `alias` in canonical digits without the `-`, `None` if it is not one
+fn normalize(alias: &str) -> Option<String> {
+    let digits = alias
+        .chars()
+        .filter(|c| *c != '-')
+        .map(|c| match c.to_ascii_lowercase() {
+            'i' | 'l' => Some('1'),
+            'o' => Some('0'),
+            c if ALPHABET.contains(&(c as u8)) && c.is_ascii() => Some(c),
+            _ => None,
+        })
+        .collect::<Option<String>>()?;
+    if digits.len() == ALIAS_DIGITS {
+        Some(digits)
+    } else {
+        None
+    }
+}
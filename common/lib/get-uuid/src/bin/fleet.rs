@@ -0,0 +1,187 @@
+//! Manages the registry of a fleet's boards, see `get_uuid::registry`
+
+use std::{
+    env,
+    io::{self, BufRead},
+    process,
+};
+
+use anyhow::{anyhow, bail};
+use get_uuid::{
+    registry::{self, Board, Registry},
+    Uuid,
+};
+
+const USAGE: &str = "\
+usage: fleet [--file <registry>] <command>
+
+commands:
+  list                       all boards
+  show <board>               one board
+  add <uuid>|--mac <mac> [--alias <alias>] [--location <location>]
+                             a new board, its UUID derived from the MAC if not given
+  set <board> [--alias <alias>] [--mac <mac>] [--location <location>]
+                             changes a board
+  remove <board>             forgets a board
+  import                     adds the boards in `mosquitto_sub -v -t '+/hello'` output on stdin
+
+<board> is a UUID, alias or MAC. The registry is `fleet.toml` unless --file or
+GET_UUID_REGISTRY say otherwise, `.json` files are written as JSON.";
+
+fn main() {
+    if let Err(e) = run(env::args().skip(1).collect()) {
+        eprintln!("error: {:#}", e);
+        process::exit(1);
+    }
+}
+
+fn run(mut args: Vec<String>) -> anyhow::Result<()> {
+    let file = take_option(&mut args, "--file")?
+        .or_else(|| env::var("GET_UUID_REGISTRY").ok())
+        .unwrap_or_else(|| "fleet.toml".to_owned());
+    let mut registry = Registry::load(&file)?;
+
+    let command = if args.is_empty() {
+        "help".to_owned()
+    } else {
+        args.remove(0)
+    };
+    match command.as_str() {
+        "list" => {
+            no_more(&args)?;
+            for board in &registry.boards {
+                print_board(board);
+            }
+            return Ok(());
+        }
+        "show" => {
+            let board = find(&registry, &args)?;
+            print_board(board);
+            return Ok(());
+        }
+        "add" => {
+            let mac = take_option(&mut args, "--mac")?
+                .map(|mac| parse_mac(&mac))
+                .transpose()?;
+            let alias = take_option(&mut args, "--alias")?;
+            let location = take_option(&mut args, "--location")?;
+            let mut board = match (args.as_slice(), mac) {
+                ([uuid], mac) => Board {
+                    mac: mac.map(registry::format_mac),
+                    ..Board::new(Uuid::parse_str(uuid).map_err(|e| anyhow!("{:?}: {}", uuid, e))?)
+                },
+                ([], Some(mac)) => Board::from_mac(mac),
+                _ => bail!("add needs a UUID or --mac\n\n{}", USAGE),
+            };
+            if let Some(alias) = alias {
+                board.alias = alias;
+            }
+            board.location = location;
+            registry.add(board)?;
+            print_board(registry.boards.last().expect("just added"));
+        }
+        "set" => {
+            let mac = take_option(&mut args, "--mac")?
+                .map(|mac| parse_mac(&mac))
+                .transpose()?;
+            let alias = take_option(&mut args, "--alias")?;
+            let location = take_option(&mut args, "--location")?;
+            let key = single(&args)?;
+            let board = registry
+                .find_mut(key)
+                .ok_or_else(|| anyhow!("no board {:?} in {}", key, file))?;
+            if let Some(mac) = mac {
+                board.mac = Some(registry::format_mac(mac));
+            }
+            if let Some(alias) = alias {
+                board.alias = alias;
+            }
+            if location.is_some() {
+                board.location = location;
+            }
+            let uuid = board.uuid;
+            registry.check()?;
+            print_board(registry.find(&uuid.to_string()).expect("just changed"));
+        }
+        "remove" => {
+            let key = single(&args)?;
+            let board = registry
+                .remove(key)
+                .ok_or_else(|| anyhow!("no board {:?} in {}", key, file))?;
+            println!("removed {}", board.alias);
+        }
+        "import" => {
+            no_more(&args)?;
+            for line in io::stdin().lock().lines() {
+                let line = line?;
+                let topic = line.split_whitespace().next().unwrap_or_default();
+                if let Some(uuid) = registry::hello_uuid(topic) {
+                    if registry.import(uuid) {
+                        println!(
+                            "added {} as {}",
+                            uuid,
+                            registry.find(&uuid.to_string()).unwrap().alias
+                        );
+                        // save as we go, `mosquitto_sub` runs until interrupted
+                        registry.save(&file)?;
+                    }
+                }
+            }
+        }
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            return Ok(());
+        }
+        unknown => bail!("unknown command {:?}\n\n{}", unknown, USAGE),
+    }
+    registry.save(&file)
+}
+
+/// Removes `--name <value>` from `args`
+fn take_option(args: &mut Vec<String>, name: &str) -> anyhow::Result<Option<String>> {
+    let index = match args.iter().position(|arg| arg == name) {
+        Some(index) => index,
+        None => return Ok(None),
+    };
+    if index + 1 >= args.len() {
+        bail!("{} needs a value", name);
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Ok(Some(value))
+}
+
+fn single(args: &[String]) -> anyhow::Result<&str> {
+    match args {
+        [arg] => Ok(arg),
+        _ => bail!("expected a single board\n\n{}", USAGE),
+    }
+}
+
+fn no_more(args: &[String]) -> anyhow::Result<()> {
+    match args {
+        [] => Ok(()),
+        _ => bail!("unexpected arguments {:?}\n\n{}", args, USAGE),
+    }
+}
+
+fn find<'r>(registry: &'r Registry, args: &[String]) -> anyhow::Result<&'r Board> {
+    let key = single(args)?;
+    registry
+        .find(key)
+        .ok_or_else(|| anyhow!("no board {:?}", key))
+}
+
+fn parse_mac(text: &str) -> anyhow::Result<[u8; 6]> {
+    registry::parse_mac(text).ok_or_else(|| anyhow!("{:?} is not a MAC", text))
+}
+
+fn print_board(board: &Board) {
+    println!(
+        "{:<20} {}  {:<17}  {}",
+        board.alias,
+        board.uuid,
+        board.mac.as_deref().unwrap_or("-"),
+        board.location.as_deref().unwrap_or("")
+    );
+}
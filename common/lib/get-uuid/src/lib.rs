@@ -1,5 +1,57 @@
-include!(concat!(env!("CARGO_MANIFEST_DIR"), "/_uuid.rs"));
+//! Identities for boards and the tools talking to them
+//!
+//! A board goes by a UUIDv5 derived from its base MAC, see `device_uuid`, so
+//! every chip keeps its identity across builds and clones of this repository.
+//! `uuid()` is the UUID fixed at build time, see the README for where it
+//! comes from. Host tools use it, and boards do too if `override = true` is
+//! set in `uuid.toml`.
+//!
+//! Where people read UUIDs, `alias` gives a short name for them, and
+//! `from_alias` finds the UUID again.
+
+mod alias;
+mod namespace;
+#[cfg(feature = "registry")]
+pub mod registry;
+
+pub use alias::{alias, from_alias, ALIAS_DIGITS};
+pub use uuid::Uuid;
+
+include!(concat!(env!("OUT_DIR"), "/_uuid.rs"));
+
+/// Default namespace of derived UUIDs, for MACs and device names
+pub const NAMESPACE: Uuid = Uuid::from_bytes(namespace::NAMESPACE);
 
 pub const fn uuid() -> &'static str {
     UUID
 }
+
+/// `uuid()`, parsed
+pub const fn typed_uuid() -> Uuid {
+    Uuid::from_bytes(UUID_BYTES)
+}
+
+/// The build-time UUID, if `uuid.toml` says boards should go by it
+pub const fn override_uuid() -> Option<Uuid> {
+    if OVERRIDE {
+        Some(typed_uuid())
+    } else {
+        None
+    }
+}
+
+/// Name-based UUID (version 5, RFC 4122) of `name` in `namespace`
+pub fn uuid_v5(namespace: &Uuid, name: &[u8]) -> Uuid {
+    Uuid::new_v5(namespace, name)
+}
+
+/// The UUID of the chip with base MAC `mac`
+pub fn from_mac(mac: [u8; 6]) -> Uuid {
+    uuid_v5(&NAMESPACE, &mac)
+}
+
+/// The UUID a board goes by: the override if there is one, or the one
+/// derived from its base MAC (`esp_efuse_mac_get_default()`)
+pub fn device_uuid(mac: [u8; 6]) -> Uuid {
+    override_uuid().unwrap_or_else(|| from_mac(mac))
+}
@@ -0,0 +1,7 @@
+// Shared with `build.rs`
+
+/// Default namespace of derived UUIDs, for MACs and device names,
+/// `uuid5(NAMESPACE_URL, "https://github.com/esp-rs/std-training/get-uuid")`
+pub const NAMESPACE: [u8; 16] = [
+    0x93, 0xfc, 0x07, 0x41, 0xf7, 0xb4, 0x51, 0xc9, 0xb4, 0x64, 0x01, 0xd8, 0x2d, 0x90, 0xa6, 0x3a,
+];
@@ -0,0 +1,256 @@
+//! A record of the boards in a fleet, kept in a local TOML or JSON file
+//!
+//! Every board has its UUID, an alias people use to address it, and
+//! optionally its base MAC and where it is. A board can be looked up by any
+//! of these but the location, see `Registry::find`.
+//!
+//! ```toml
+//! [[board]]
+//! uuid = "371c0d6c-1712-41c3-95bb-e84bf5c0ca3b"
+//! alias = "desk-board"
+//! mac = "7c:df:a1:00:11:22"
+//! location = "lab, shelf 2"
+//! ```
+
+use std::{fmt::Write, fs, io::ErrorKind, path::Path};
+
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Board {
+    pub uuid: Uuid,
+    /// `alias(uuid)` unless renamed
+    pub alias: String,
+    /// base MAC, e.g. `7c:df:a1:00:11:22`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+}
+
+impl Board {
+    /// A board going by its derived alias
+    pub fn new(uuid: Uuid) -> Self {
+        Self {
+            uuid,
+            alias: crate::alias(&uuid),
+            mac: None,
+            location: None,
+        }
+    }
+
+    /// The board with base MAC `mac`, unless `uuid.toml` overrode its UUID
+    pub fn from_mac(mac: [u8; 6]) -> Self {
+        Self {
+            mac: Some(format_mac(mac)),
+            ..Self::new(crate::from_mac(mac))
+        }
+    }
+}
+
+/// How the registry file is written, by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Toml,
+    Json,
+}
+
+impl Format {
+    /// JSON for `.json` files, TOML for everything else
+    pub fn of(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("json") => Format::Json,
+            _ => Format::Toml,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Registry {
+    #[serde(default, rename = "board")]
+    pub boards: Vec<Board>,
+}
+
+impl Registry {
+    /// Reads the registry at `path`, an empty one if there is no file yet
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("could not read {}", path.display())),
+        };
+        let registry = Self::parse(&text, Format::of(path))
+            .with_context(|| format!("{} is not a valid registry", path.display()))?;
+        Ok(registry)
+    }
+
+    pub fn parse(text: &str, format: Format) -> anyhow::Result<Self> {
+        let registry: Self = match format {
+            Format::Toml => toml::from_str(text)?,
+            Format::Json => serde_json::from_str(text)?,
+        };
+        registry.check()?;
+        Ok(registry)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        fs::write(path, self.to_text(Format::of(path))?)
+            .with_context(|| format!("could not write {}", path.display()))
+    }
+
+    pub fn to_text(&self, format: Format) -> anyhow::Result<String> {
+        Ok(match format {
+            Format::Toml => toml::to_string(self)?,
+            Format::Json => serde_json::to_string_pretty(self)? + "\n",
+        })
+    }
+
+    /// The board with UUID, alias, derived alias or MAC `key`
+    ///
+    /// Aliases ignore case, MACs may be written with `:`, `-` or nothing
+    /// between the bytes.
+    pub fn find(&self, key: &str) -> Option<&Board> {
+        let index = self.position(key)?;
+        Some(&self.boards[index])
+    }
+
+    pub fn find_mut(&mut self, key: &str) -> Option<&mut Board> {
+        let index = self.position(key)?;
+        Some(&mut self.boards[index])
+    }
+
+    fn position(&self, key: &str) -> Option<usize> {
+        if let Ok(uuid) = Uuid::parse_str(key) {
+            return self.boards.iter().position(|board| board.uuid == uuid);
+        }
+        if let Some(index) = self
+            .boards
+            .iter()
+            .position(|board| board.alias.eq_ignore_ascii_case(key))
+        {
+            return Some(index);
+        }
+        if let Some(mac) = parse_mac(key) {
+            let mac = format_mac(mac);
+            return self
+                .boards
+                .iter()
+                .position(|board| board.mac.as_deref() == Some(&mac));
+        }
+        let uuid = crate::from_alias(key, self.boards.iter().map(|board| board.uuid))?;
+        self.boards.iter().position(|board| board.uuid == uuid)
+    }
+
+    /// Adds `board`, unless its UUID or alias is already taken
+    pub fn add(&mut self, board: Board) -> anyhow::Result<()> {
+        self.boards.push(board);
+        if let Err(e) = self.check() {
+            self.boards.pop();
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Board> {
+        let index = self.position(key)?;
+        Some(self.boards.remove(index))
+    }
+
+    /// Adds a board that said hello, returning whether it is new
+    pub fn import(&mut self, uuid: Uuid) -> bool {
+        if self.boards.iter().any(|board| board.uuid == uuid) {
+            return false;
+        }
+        let mut board = Board::new(uuid);
+        // someone renamed another board to this alias, keep them apart
+        if self.find(&board.alias).is_some() {
+            board.alias = uuid.to_string();
+        }
+        self.boards.push(board);
+        true
+    }
+
+    /// Rejects registries where boards share a UUID, alias or MAC
+    pub fn check(&self) -> anyhow::Result<()> {
+        for (i, board) in self.boards.iter().enumerate() {
+            if board.alias.is_empty() {
+                bail!("board {} has an empty alias", board.uuid);
+            }
+            if let Some(mac) = &board.mac {
+                if parse_mac(mac).is_none() {
+                    bail!("board {} has an invalid MAC {:?}", board.uuid, mac);
+                }
+            }
+            for other in &self.boards[..i] {
+                if other.uuid == board.uuid {
+                    bail!("board {} is listed twice", board.uuid);
+                }
+                if other.alias.eq_ignore_ascii_case(&board.alias) {
+                    bail!(
+                        "boards {} and {} share the alias {:?}",
+                        other.uuid,
+                        board.uuid,
+                        board.alias
+                    );
+                }
+                if board.mac.is_some()
+                    && other.mac.as_ref().and_then(|mac| parse_mac(mac))
+                        == board.mac.as_ref().and_then(|mac| parse_mac(mac))
+                {
+                    bail!("boards {} and {} share a MAC", other.uuid, board.uuid);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The UUID of the board that published `topic` on its `hello_topic`
+///
+/// Works with and without a topic prefix, the UUID is the level before `hello`.
+pub fn hello_uuid(topic: &str) -> Option<Uuid> {
+    let uuid = topic.strip_suffix("/hello")?.rsplit('/').next()?;
+    Uuid::parse_str(uuid).ok()
+}
+
+/// Parses `7c:df:a1:00:11:22`, also with `-` or nothing between the bytes
+///
+/// Every byte is exactly two hex digits, and the separator is the same
+/// throughout.
+pub fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let text = text.as_bytes();
+    let separator = match text.len() {
+        12 => None,
+        17 if matches!(text[2], b':' | b'-') => Some(text[2]),
+        _ => return None,
+    };
+    let stride = if separator.is_some() { 3 } else { 2 };
+    let hex = |digit: u8| char::from(digit).to_digit(16);
+    let mut mac = [0; 6];
+    for (i, byte) in mac.iter_mut().enumerate() {
+        let at = i * stride;
+        if let Some(separator) = separator {
+            if i > 0 && text[at - 1] != separator {
+                return None;
+            }
+        }
+        *byte = (hex(text[at])? << 4 | hex(text[at + 1])?) as u8;
+    }
+    Some(mac)
+}
+
+/// Lower case with `:` between the bytes
+pub fn format_mac(mac: [u8; 6]) -> String {
+    let mut text = String::with_capacity(17);
+    for (i, byte) in mac.iter().enumerate() {
+        if i > 0 {
+            text.push(':');
+        }
+        write!(text, "{:02x}", byte).expect("writing to a String never fails");
+    }
+    text
+}
@@ -0,0 +1,43 @@
+use get_uuid::{alias, from_alias, from_mac, Uuid, ALIAS_DIGITS};
+
+fn uuid(text: &str) -> Uuid {
+    Uuid::parse_str(text).unwrap()
+}
+
+#[test]
+fn alias_is_first_40_bits_in_base32() {
+    assert_eq!(alias(&Uuid::nil()), "0000-0000");
+    assert_eq!(
+        alias(&uuid("ffffffff-ff00-0000-0000-000000000000")),
+        "zzzz-zzzz"
+    );
+    // 0x371c0d6c17 = 00110 11100 01110 00000 11010 11011 00000 10111
+    assert_eq!(
+        alias(&uuid("371c0d6c-1712-41c3-95bb-e84bf5c0ca3b")),
+        "6we0-tv0q"
+    );
+    let alias = alias(&from_mac([0x7c, 0xdf, 0xa1, 0x00, 0x11, 0x22]));
+    assert_eq!(alias.len(), ALIAS_DIGITS + 1);
+}
+
+#[test]
+fn from_alias_finds_known_uuid() {
+    let board = uuid("371c0d6c-1712-41c3-95bb-e84bf5c0ca3b");
+    let other = uuid("886313e1-3b8a-5372-9b90-0c9aee199e5d");
+    let fleet = [other, board];
+    assert_eq!(from_alias("6we0-tv0q", fleet), Some(board));
+    assert_eq!(from_alias(&alias(&other), fleet), Some(other));
+    assert_eq!(from_alias("6we0-tv0r", fleet), None);
+}
+
+#[test]
+fn from_alias_forgives_typing() {
+    let board = uuid("371c0d6c-1712-41c3-95bb-e84bf5c0ca3b");
+    for typed in ["6WE0-TV0Q", "6we0tv0q", "6weo-tvOq"] {
+        assert_eq!(from_alias(typed, [board]), Some(board), "{}", typed);
+    }
+    // `u` is not a digit, and lengths have to match
+    for typed in ["6we0-tvuq", "6we0-tv0", "6we0-tv0qq", ""] {
+        assert_eq!(from_alias(typed, [board]), None, "{}", typed);
+    }
+}
@@ -0,0 +1,170 @@
+// `build.rs` logic, included the same way the build script does
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use uuid::Uuid;
+
+#[path = "../build/identity.rs"]
+mod identity;
+#[path = "../src/namespace.rs"]
+mod namespace;
+
+use identity::{resolve, Identity};
+
+fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+    let vars: HashMap<_, _> = vars.iter().copied().collect();
+    move |name| vars.get(name).map(|value| value.to_string())
+}
+
+const FILE: &str = "[get-uuid]\nuuid = \"371c0d6c-1712-41c3-95bb-e84bf5c0ca3b\"\n";
+
+#[test]
+fn uuid_file_is_in_package_root() {
+    let root = Path::new("/work/get-uuid");
+    assert_eq!(
+        identity::uuid_file(root, &env(&[])),
+        Path::new("/work/get-uuid/uuid.toml")
+    );
+    assert_eq!(
+        identity::uuid_file(root, &env(&[("GET_UUID_FILE", "ids/board.toml")])),
+        Path::new("/work/get-uuid/ids/board.toml")
+    );
+    assert_eq!(
+        identity::uuid_file(root, &env(&[("GET_UUID_FILE", "/etc/board.toml")])),
+        Path::new("/etc/board.toml")
+    );
+}
+
+#[test]
+fn reruns_on_file_and_variables() {
+    let directives = identity::rerun_directives(Some(Path::new("/work/get-uuid/uuid.toml")));
+    assert!(directives.contains(&"cargo:rerun-if-changed=/work/get-uuid/uuid.toml".to_owned()));
+    for var in identity::VARS {
+        assert!(directives.contains(&format!("cargo:rerun-if-env-changed={}", var)));
+    }
+}
+
+#[test]
+fn missing_file_is_not_watched() {
+    let directives = identity::rerun_directives(None);
+    assert!(directives
+        .iter()
+        .all(|directive| !directive.starts_with("cargo:rerun-if-changed=")));
+    assert_eq!(directives.len(), identity::VARS.len());
+}
+
+#[test]
+fn new_file_resolves_to_its_uuid() {
+    let uuid = Uuid::parse_str("371c0d6c-1712-41c3-95bb-e84bf5c0ca3b").unwrap();
+    let identity = resolve(Some(&identity::new_file(uuid)), &env(&[])).unwrap();
+    assert_eq!(
+        identity,
+        Identity {
+            uuid,
+            is_override: false
+        }
+    );
+}
+
+#[test]
+fn first_source_wins() {
+    let device = "[get-uuid]\ndevice_name = \"desk-board\"\n";
+    let derived = "fbc98013-7ab4-5244-8ee2-ccf492bb9476";
+    let explicit = "0b5f8f0e-1c4b-4a55-9d0e-6b1d3f0f6b0a";
+
+    let uuid = |file: Option<&str>, vars: &[(&str, &str)]| {
+        resolve(file, &env(vars)).unwrap().uuid.to_string()
+    };
+    assert_eq!(
+        uuid(Some(FILE), &[]),
+        "371c0d6c-1712-41c3-95bb-e84bf5c0ca3b"
+    );
+    assert_eq!(uuid(Some(device), &[]), derived);
+    assert_eq!(
+        uuid(Some(FILE), &[("GET_UUID_DEVICE_NAME", "desk-board")]),
+        derived
+    );
+    assert_eq!(
+        uuid(None, &[("GET_UUID_DEVICE_NAME", "desk-board")]),
+        derived
+    );
+    let both = [
+        ("GET_UUID", explicit),
+        ("GET_UUID_DEVICE_NAME", "desk-board"),
+    ];
+    assert_eq!(uuid(Some(device), &both), explicit);
+}
+
+#[test]
+fn namespace_changes_derived_uuid() {
+    let default = resolve(None, &env(&[("GET_UUID_DEVICE_NAME", "desk-board")])).unwrap();
+    let file = "[get-uuid]\ndevice_name = \"desk-board\"\n\
+                namespace = \"6ba7b811-9dad-11d1-80b4-00c04fd430c8\"\n";
+    let other = resolve(Some(file), &env(&[])).unwrap();
+    assert_ne!(default.uuid, other.uuid);
+    assert_eq!(other.uuid.get_version_num(), 5);
+}
+
+#[test]
+fn malformed_values_fail() {
+    let fails = |file: Option<&str>, vars: &[(&str, &str)]| resolve(file, &env(vars)).is_err();
+    assert!(fails(Some(FILE), &[("GET_UUID", "not-a-uuid")]));
+    // the simple form without hyphens is not accepted either
+    assert!(fails(
+        None,
+        &[("GET_UUID", "371c0d6c171241c395bbe84bf5c0ca3b")]
+    ));
+    assert!(fails(Some("[get-uuid]\nuuid = \"371c0d6c\"\n"), &[]));
+    assert!(fails(Some("[get-uuid]\nuuid = 42\n"), &[]));
+    assert!(fails(Some("[get-uuid]\ndevice_name = \"\"\n"), &[]));
+    assert!(fails(
+        Some("[get-uuid]\ndevice_name = \"a\"\nnamespace = \"x\"\n"),
+        &[]
+    ));
+    assert!(fails(
+        Some("[get-uuid]\nuuid = \"371c0d6c-1712-41c3-95bb-e84bf5c0ca3b\"\noverride = \"yes\"\n"),
+        &[]
+    ));
+    assert!(fails(Some("uuid = "), &[]));
+    assert!(fails(Some("[other]\n"), &[]));
+    assert!(fails(None, &[]));
+}
+
+#[test]
+fn errors_say_what_is_wrong() {
+    let error = resolve(None, &env(&[("GET_UUID", "nope")])).unwrap_err();
+    assert!(format!("{:#}", error).starts_with("GET_UUID is not a valid UUID"));
+}
+
+#[test]
+fn source_defines_constants() {
+    let identity = resolve(
+        Some("[get-uuid]\nuuid = \"371c0d6c-1712-41c3-95bb-e84bf5c0ca3b\"\noverride = true\n"),
+        &env(&[]),
+    )
+    .unwrap();
+    assert_eq!(
+        identity::source(&identity),
+        "const UUID: &str = \"371c0d6c-1712-41c3-95bb-e84bf5c0ca3b\";\n\
+         const UUID_BYTES: [u8; 16] = [55, 28, 13, 108, 23, 18, 65, 195, 149, 187, 232, 75, 245, 192, 202, 59];\n\
+         const OVERRIDE: bool = true;\n"
+    );
+}
+
+#[test]
+fn environment_alone_can_decide() {
+    assert!(!identity::from_env(&env(&[])));
+    assert!(!identity::from_env(&env(&[("GET_UUID_NAMESPACE", "x")])));
+    assert!(identity::from_env(&env(&[(
+        "GET_UUID_DEVICE_NAME",
+        "desk-board"
+    )])));
+    assert!(identity::from_env(&env(&[("GET_UUID", "x")])));
+}
+
+#[test]
+fn library_includes_generated_source() {
+    let include = format!("concat!(env!(\"OUT_DIR\"), \"/{}\")", identity::SOURCE_FILE);
+    assert!(include_str!("../src/lib.rs").contains(&include));
+}
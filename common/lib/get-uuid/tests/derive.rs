@@ -0,0 +1,35 @@
+use get_uuid::{from_mac, uuid_v5, Uuid, NAMESPACE};
+
+#[test]
+fn matches_reference_v5() {
+    let uuid = uuid_v5(&Uuid::NAMESPACE_DNS, b"python.org");
+    assert_eq!(uuid.to_string(), "886313e1-3b8a-5372-9b90-0c9aee199e5d");
+}
+
+#[test]
+fn namespace_is_derived_from_project_url() {
+    let namespace = uuid_v5(
+        &Uuid::NAMESPACE_URL,
+        b"https://github.com/esp-rs/std-training/get-uuid",
+    );
+    assert_eq!(namespace, NAMESPACE);
+}
+
+#[test]
+fn mac_uuid_is_stable() {
+    let mac = [0x7c, 0xdf, 0xa1, 0x00, 0x11, 0x22];
+    assert_eq!(
+        from_mac(mac).to_string(),
+        "c8f24e4d-0642-5927-a80c-a67ec16e047f"
+    );
+    assert_eq!(from_mac(mac), from_mac(mac));
+    assert_ne!(
+        from_mac(mac),
+        from_mac([0x7c, 0xdf, 0xa1, 0x00, 0x11, 0x23])
+    );
+}
+
+#[test]
+fn typed_uuid_matches_text() {
+    assert_eq!(get_uuid::typed_uuid().to_string(), get_uuid::uuid());
+}
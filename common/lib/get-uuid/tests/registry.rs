@@ -0,0 +1,118 @@
+use get_uuid::{
+    alias, from_mac,
+    registry::{self, Board, Format, Registry},
+    Uuid,
+};
+
+const BOARD: &str = "371c0d6c-1712-41c3-95bb-e84bf5c0ca3b";
+const MAC: [u8; 6] = [0x7c, 0xdf, 0xa1, 0x00, 0x11, 0x22];
+
+fn uuid(text: &str) -> Uuid {
+    Uuid::parse_str(text).unwrap()
+}
+
+fn fleet() -> Registry {
+    let mut registry = Registry::default();
+    registry.add(Board::new(uuid(BOARD))).unwrap();
+    let mut desk = Board::from_mac(MAC);
+    desk.alias = "desk-board".to_owned();
+    desk.location = Some("lab, shelf 2".to_owned());
+    registry.add(desk).unwrap();
+    registry
+}
+
+#[test]
+fn finds_boards_by_any_key() {
+    let registry = fleet();
+    let desk = from_mac(MAC);
+    assert_eq!(registry.find(BOARD).unwrap().uuid, uuid(BOARD));
+    assert_eq!(registry.find("6we0-tv0q").unwrap().uuid, uuid(BOARD));
+    assert_eq!(registry.find("Desk-Board").unwrap().uuid, desk);
+    assert_eq!(registry.find("7C-DF-A1-00-11-22").unwrap().uuid, desk);
+    assert_eq!(registry.find("7cdfa1001122").unwrap().uuid, desk);
+    // renamed boards still answer to the alias derived from their UUID
+    assert_eq!(registry.find(&alias(&desk)).unwrap().uuid, desk);
+    assert!(registry.find("nowhere").is_none());
+}
+
+#[test]
+fn round_trips_through_both_formats() {
+    let registry = fleet();
+    for format in [Format::Toml, Format::Json] {
+        let text = registry.to_text(format).unwrap();
+        assert_eq!(Registry::parse(&text, format).unwrap(), registry);
+    }
+    let toml = registry.to_text(Format::Toml).unwrap();
+    assert!(toml.contains("[[board]]"));
+    assert!(toml.contains("mac = \"7c:df:a1:00:11:22\""));
+}
+
+#[test]
+fn format_follows_extension() {
+    assert_eq!(Format::of("fleet.json".as_ref()), Format::Json);
+    assert_eq!(Format::of("fleet.JSON".as_ref()), Format::Json);
+    assert_eq!(Format::of("fleet.toml".as_ref()), Format::Toml);
+    assert_eq!(Format::of("fleet".as_ref()), Format::Toml);
+}
+
+#[test]
+fn rejects_duplicates() {
+    let mut registry = fleet();
+    assert!(registry.add(Board::new(uuid(BOARD))).is_err());
+    let mut twin = Board::new(uuid("886313e1-3b8a-5372-9b90-0c9aee199e5d"));
+    twin.alias = "DESK-BOARD".to_owned();
+    assert!(registry.add(twin.clone()).is_err());
+    twin.alias = "other".to_owned();
+    twin.mac = Some("7c:df:a1:00:11:22".to_owned());
+    assert!(registry.add(twin).is_err());
+    assert_eq!(registry, fleet());
+}
+
+#[test]
+fn imports_boards_from_hello_topics() {
+    let mut registry = fleet();
+    let new = uuid("886313e1-3b8a-5372-9b90-0c9aee199e5d");
+    assert_eq!(registry::hello_uuid(&format!("{}/hello", new)), Some(new));
+    assert_eq!(
+        registry::hello_uuid(&format!("org/site/{}/hello", new)),
+        Some(new)
+    );
+    assert_eq!(registry::hello_uuid(&format!("{}/status", new)), None);
+    assert_eq!(registry::hello_uuid("desk-board/hello"), None);
+
+    assert!(registry.import(new));
+    assert!(!registry.import(new));
+    assert!(!registry.import(uuid(BOARD)));
+    assert_eq!(registry.find(&alias(&new)).unwrap().uuid, new);
+}
+
+#[test]
+fn removes_boards() {
+    let mut registry = fleet();
+    assert_eq!(registry.remove("desk-board").unwrap().uuid, from_mac(MAC));
+    assert!(registry.remove("desk-board").is_none());
+    assert_eq!(registry.boards.len(), 1);
+}
+
+#[test]
+fn macs_parse_and_format() {
+    assert_eq!(registry::parse_mac("7c:df:a1:00:11:22"), Some(MAC));
+    assert_eq!(registry::parse_mac("7c:df:a1:00:11"), None);
+    assert_eq!(registry::parse_mac("7C-DF-A1-00-11-22"), Some(MAC));
+    assert_eq!(registry::parse_mac("7cdfa1001122"), Some(MAC));
+    for text in [
+        "7c:df:a1:00:11:2g",
+        // `u8::from_str_radix` takes a sign
+        "7c:df:a1:00:11:+2",
+        "+7cdfa100112",
+        "7c:df:a1:00:11:22:",
+        "7c:df-a1:00:11:22",
+        "7c:df:a1:00:1122:",
+        "7cd:fa1:00:11:22",
+        "7c df a1 00 11 22",
+        "7c:df:a1:00:11:ä",
+    ] {
+        assert_eq!(registry::parse_mac(text), None, "{}", text);
+    }
+    assert_eq!(registry::format_mac(MAC), "7c:df:a1:00:11:22");
+}
@@ -0,0 +1,242 @@
+//! Command acknowledgements
+//!
+//! A host that wants to know whether a command was applied sends it as a
+//! `CommandRequest` with a correlation `id`. The board answers with an `Ack`
+//! carrying the same `id` on `response_topic(uuid, id)`, so the host can
+//! subscribe to `response_filter(uuid)` and wait for it with a timeout.
+//! Plain `Command` payloads are still accepted, they just never get an `Ack`,
+//! and neither do requests that cannot be decoded.
+
+use core::fmt::{self, Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::{Envelope, MessageType};
+use crate::{Codec, Command, CommandKind, ConvertError, MAX_COMMAND_LEN};
+
+const ID_LEN: usize = 4;
+
+/// Largest binary `CommandRequest` body
+const MAX_REQUEST_LEN: usize = ID_LEN + MAX_COMMAND_LEN;
+
+pub fn write_response_topic(topic: &mut impl Write, uuid: &str, id: u32) -> fmt::Result {
+    write!(topic, "{}/response/{}", uuid, id)
+}
+
+#[cfg(feature = "std")]
+pub fn response_topic(uuid: &str, id: u32) -> String {
+    crate::to_topic(|topic| write_response_topic(topic, uuid, id))
+}
+
+/// Matches the responses to all of a board's requests
+pub fn write_response_filter(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/response/+", uuid)
+}
+
+#[cfg(feature = "std")]
+pub fn response_filter(uuid: &str) -> String {
+    crate::to_topic(|topic| write_response_filter(topic, uuid))
+}
+
+/// A `Command` with an optional correlation ID
+///
+/// Binary: enveloped `MessageType::CommandRequest`, id (u32, big endian)
+/// followed by the command data. Without an `id` this is a bare `Command`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CommandRequest {
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub id: Option<u32>,
+    pub command: Command,
+}
+
+impl CommandRequest {
+    pub fn new(id: u32, command: Command) -> Self {
+        Self {
+            id: Some(id),
+            command,
+        }
+    }
+
+    /// Writes the binary payload to `buf`, returning the number of bytes used
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, ConvertError> {
+        let data = self.command.data();
+        match self.id {
+            Some(id) => {
+                let mut body = [0; MAX_REQUEST_LEN];
+                body[..ID_LEN].copy_from_slice(&id.to_be_bytes());
+                body[ID_LEN..ID_LEN + data.len()].copy_from_slice(&data);
+                Envelope::new(MessageType::CommandRequest, &body[..ID_LEN + data.len()])
+                    .encode_into(buf)
+            }
+            None => {
+                let buf = buf
+                    .get_mut(..data.len())
+                    .ok_or(ConvertError::BufferTooSmall(data.len()))?;
+                buf.copy_from_slice(&data);
+                Ok(data.len())
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode_with(&self, codec: Codec) -> Vec<u8> {
+        match codec {
+            Codec::Binary => {
+                let mut data = [0; crate::envelope::HEADER_LEN + MAX_REQUEST_LEN];
+                let len = self
+                    .encode_into(&mut data)
+                    .expect("request fits its maximum size");
+                data[..len].to_vec()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => serde_json::to_vec(self).expect("requests always serialize"),
+        }
+    }
+
+    /// Decodes a request, or a bare `Command` as a request without `id`
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        Self::decode_kind_with(codec, CommandKind::BoardLed, data)
+    }
+
+    /// Decodes a request received on the topic of a `kind` command
+    pub fn decode_kind_with(
+        codec: Codec,
+        kind: CommandKind,
+        data: &[u8],
+    ) -> Result<Self, ConvertError> {
+        let request = match codec {
+            Codec::Binary => match Envelope::decode(data) {
+                Ok(envelope) if envelope.message_type == MessageType::CommandRequest => {
+                    let body = envelope.body;
+                    if body.len() < ID_LEN {
+                        return Err(ConvertError::Length(body.len()));
+                    }
+                    let id = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+                    Self::new(id, Command::decode_kind(kind, &body[ID_LEN..])?)
+                }
+                _ => Self {
+                    id: None,
+                    command: Command::decode_kind(kind, data)?,
+                },
+            },
+            #[cfg(feature = "serde")]
+            Codec::Json => {
+                let request: Self = match serde_json::from_slice(data) {
+                    Ok(request) => request,
+                    Err(_) => Self {
+                        id: None,
+                        command: serde_json::from_slice(data)?,
+                    },
+                };
+                request.command.validate()?;
+                request
+            }
+        };
+        if request.command.kind() != kind {
+            return Err(ConvertError::InvalidPath);
+        }
+        Ok(request)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum AckStatus {
+    /// the command was applied
+    Ok = 0,
+    /// the command was understood but refused, e.g. a setting out of range
+    Rejected = 1,
+    /// the command was understood but applying it failed
+    Failed = 2,
+    /// this board does not support the command
+    Unsupported = 3,
+}
+
+impl TryFrom<u8> for AckStatus {
+    type Error = ConvertError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AckStatus::Ok),
+            1 => Ok(AckStatus::Rejected),
+            2 => Ok(AckStatus::Failed),
+            3 => Ok(AckStatus::Unsupported),
+            _ => Err(ConvertError::InvalidData),
+        }
+    }
+}
+
+/// The board's answer to a `CommandRequest`
+///
+/// Binary: enveloped `MessageType::Ack`, id (u32, big endian), status (u8)
+/// and `detail` as UTF-8 in the remaining bytes.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Ack {
+    pub id: u32,
+    pub status: AckStatus,
+    /// human readable, may be empty
+    pub detail: String,
+}
+
+#[cfg(feature = "std")]
+impl Ack {
+    pub fn new(id: u32, status: AckStatus, detail: impl Into<String>) -> Self {
+        Self {
+            id,
+            status,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn topic(&self, uuid: &str) -> String {
+        response_topic(uuid, self.id)
+    }
+
+    pub fn encode_with(&self, codec: Codec) -> Result<Vec<u8>, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let mut body = Vec::with_capacity(ID_LEN + 1 + self.detail.len());
+                body.extend_from_slice(&self.id.to_be_bytes());
+                body.push(self.status as u8);
+                body.extend_from_slice(self.detail.as_bytes());
+                Envelope::new(MessageType::Ack, &body).encode()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::to_vec(self)?),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let envelope = Envelope::decode(data)?;
+                if envelope.message_type != MessageType::Ack {
+                    return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+                }
+                let body = envelope.body;
+                if body.len() < ID_LEN + 1 {
+                    return Err(ConvertError::Length(body.len()));
+                }
+                let id = u32::from_be_bytes([body[0], body[1], body[2], body[3]]);
+                let status = AckStatus::try_from(body[ID_LEN])?;
+                let detail = std::str::from_utf8(&body[ID_LEN + 1..])
+                    .map_err(|_| ConvertError::InvalidData)?;
+                Ok(Self::new(id, status, detail))
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
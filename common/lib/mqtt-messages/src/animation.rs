@@ -0,0 +1,273 @@
+//! LED patterns the board plays on its own
+//!
+//! Instead of publishing one color per frame, a host sends a single
+//! `Command::Animation` and the board renders every frame with
+//! `Animation::color_at`.
+//!
+//! Binary: pattern (u8), period in ms (u32, big endian), repeat count
+//! (u16, big endian) and priority (u8), followed by the pattern data:
+//! r, g, b for `Solid`, `Blink` and `Breathe`, nothing for `Rainbow`, and a
+//! count (u8) followed by time in ms (u32, big endian), r, g, b per keyframe
+//! for `Keyframes`.
+
+use heapless::Vec;
+use rgb::RGB8;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ConvertError;
+
+/// Most keyframes a single animation can have
+pub const MAX_KEYFRAMES: usize = 8;
+
+const HEADER_LEN: usize = 1 + 4 + 2 + 1;
+const KEYFRAME_LEN: usize = 4 + 3;
+
+/// Longest binary `Animation` in bytes
+pub const MAX_LEN: usize = HEADER_LEN + 1 + MAX_KEYFRAMES * KEYFRAME_LEN;
+
+const BLACK: RGB8 = RGB8::new(0, 0, 0);
+
+/// The color at a point in time of an animation's period
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Keyframe {
+    /// milliseconds since the start of the period
+    pub at_ms: u32,
+    pub color: RGB8,
+}
+
+impl Keyframe {
+    pub fn new(at_ms: u32, color: RGB8) -> Self {
+        Self { at_ms, color }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Pattern {
+    /// the same color all the time
+    Solid(RGB8),
+    /// on for the first half of the period, off for the second
+    Blink(RGB8),
+    /// fades in and out once per period
+    Breathe(RGB8),
+    /// cycles through all hues once per period
+    Rainbow,
+    /// fades from keyframe to keyframe, and from the last back to the first
+    Keyframes(Vec<Keyframe, MAX_KEYFRAMES>),
+}
+
+impl Pattern {
+    fn code(&self) -> u8 {
+        match self {
+            Pattern::Solid(_) => 0,
+            Pattern::Blink(_) => 1,
+            Pattern::Breathe(_) => 2,
+            Pattern::Rainbow => 3,
+            Pattern::Keyframes(_) => 4,
+        }
+    }
+
+    /// The color `t` ms into a period of `period` ms
+    fn color_at(&self, t: u32, period: u32) -> RGB8 {
+        match self {
+            Pattern::Solid(color) => *color,
+            Pattern::Blink(color) if t < period / 2 => *color,
+            Pattern::Blink(_) => BLACK,
+            Pattern::Breathe(color) => {
+                // up and down once per period, squared so it looks even
+                let level = (t as u64 * 510 / period as u64) as u32;
+                let level = if level > 255 { 510 - level } else { level };
+                scale(*color, (level * level / 255) as u8)
+            }
+            Pattern::Rainbow => hue(t as u64 * 1536 / period as u64),
+            Pattern::Keyframes(keyframes) if keyframes.is_empty() => BLACK,
+            Pattern::Keyframes(keyframes) => {
+                // the keyframes before and after `t`, wrapping around the period
+                let next = keyframes.iter().position(|keyframe| keyframe.at_ms > t);
+                let (from, to) = match next {
+                    Some(0) | None => (keyframes[keyframes.len() - 1], keyframes[0]),
+                    Some(next) => (keyframes[next - 1], keyframes[next]),
+                };
+                let period = period as u64;
+                let from_at = from.at_ms as u64 % period;
+                let to_at = to.at_ms as u64 % period;
+                // a single keyframe spans the whole period
+                let span = (to_at + period - from_at - 1) % period + 1;
+                let into = (t as u64 + period - from_at) % period;
+                lerp(from.color, to.color, into, span)
+            }
+        }
+    }
+}
+
+/// A pattern, repeated `repeat` times (`0` for ever)
+///
+/// A board plays one animation at a time. A new animation replaces the
+/// running one unless that has a higher `priority`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Animation {
+    pub pattern: Pattern,
+    pub period_ms: u32,
+    pub repeat: u16,
+    pub priority: u8,
+}
+
+impl Animation {
+    /// The color `elapsed_ms` after the start, `None` once it is over
+    pub fn color_at(&self, elapsed_ms: u64) -> Option<RGB8> {
+        let period = self.period_ms.max(1);
+        if self.repeat != 0 && elapsed_ms >= period as u64 * self.repeat as u64 {
+            return None;
+        }
+        let t = (elapsed_ms % period as u64) as u32;
+        Some(self.pattern.color_at(t, period))
+    }
+
+    /// Writes the binary payload to `buf`, returning the number of bytes used
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, ConvertError> {
+        let len = match &self.pattern {
+            Pattern::Rainbow => HEADER_LEN,
+            Pattern::Solid(_) | Pattern::Blink(_) | Pattern::Breathe(_) => HEADER_LEN + 3,
+            Pattern::Keyframes(keyframes) => HEADER_LEN + 1 + keyframes.len() * KEYFRAME_LEN,
+        };
+        let buf = buf
+            .get_mut(..len)
+            .ok_or(ConvertError::BufferTooSmall(len))?;
+
+        buf[0] = self.pattern.code();
+        buf[1..5].copy_from_slice(&self.period_ms.to_be_bytes());
+        buf[5..7].copy_from_slice(&self.repeat.to_be_bytes());
+        buf[7] = self.priority;
+        let data = &mut buf[HEADER_LEN..];
+        match &self.pattern {
+            Pattern::Solid(color) | Pattern::Blink(color) | Pattern::Breathe(color) => {
+                data.copy_from_slice(color.as_ref())
+            }
+            Pattern::Rainbow => {}
+            Pattern::Keyframes(keyframes) => {
+                data[0] = keyframes.len() as u8;
+                for (keyframe, data) in keyframes.iter().zip(data[1..].chunks_mut(KEYFRAME_LEN)) {
+                    data[..4].copy_from_slice(&keyframe.at_ms.to_be_bytes());
+                    data[4..].copy_from_slice(keyframe.color.as_ref());
+                }
+            }
+        }
+        Ok(len)
+    }
+
+    /// Rejects animations that cannot be played
+    ///
+    /// A playable animation has a non-zero period, and at least one keyframe
+    /// if it has keyframes, in ascending order within the period.
+    pub fn validate(&self) -> Result<(), ConvertError> {
+        if self.period_ms == 0 {
+            return Err(ConvertError::InvalidData);
+        }
+        if let Pattern::Keyframes(keyframes) = &self.pattern {
+            let ascending = keyframes
+                .windows(2)
+                .all(|pair| pair[0].at_ms < pair[1].at_ms);
+            let within = keyframes
+                .iter()
+                .all(|keyframe| keyframe.at_ms < self.period_ms);
+            if keyframes.is_empty() || !ascending || !within {
+                return Err(ConvertError::InvalidData);
+            }
+        }
+        Ok(())
+    }
+
+    /// Decodes a binary payload, rejecting animations that cannot be played
+    pub fn decode(data: &[u8]) -> Result<Self, ConvertError> {
+        if data.len() < HEADER_LEN {
+            return Err(ConvertError::Length(data.len()));
+        }
+        let code = data[0];
+        let period_ms = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        let repeat = u16::from_be_bytes([data[5], data[6]]);
+        let priority = data[7];
+        let data = &data[HEADER_LEN..];
+        let color = || match data {
+            [r, g, b] => Ok(RGB8::new(*r, *g, *b)),
+            _ => Err(ConvertError::Length(data.len())),
+        };
+        let pattern = match code {
+            0 => Pattern::Solid(color()?),
+            1 => Pattern::Blink(color()?),
+            2 => Pattern::Breathe(color()?),
+            3 if data.is_empty() => Pattern::Rainbow,
+            3 => return Err(ConvertError::Length(data.len())),
+            4 => Pattern::Keyframes(decode_keyframes(data)?),
+            _ => return Err(ConvertError::InvalidData),
+        };
+        let animation = Self {
+            pattern,
+            period_ms,
+            repeat,
+            priority,
+        };
+        animation.validate()?;
+        Ok(animation)
+    }
+}
+
+fn decode_keyframes(data: &[u8]) -> Result<Vec<Keyframe, MAX_KEYFRAMES>, ConvertError> {
+    let (count, data) = data.split_first().ok_or(ConvertError::Length(0))?;
+    let count = *count as usize;
+    if count > MAX_KEYFRAMES {
+        return Err(ConvertError::InvalidData);
+    }
+    if data.len() != count * KEYFRAME_LEN {
+        return Err(ConvertError::Length(data.len()));
+    }
+    let mut keyframes: Vec<Keyframe, MAX_KEYFRAMES> = Vec::new();
+    for data in data.chunks(KEYFRAME_LEN) {
+        let at_ms = u32::from_be_bytes([data[0], data[1], data[2], data[3]]);
+        let color = RGB8::new(data[4], data[5], data[6]);
+        keyframes
+            .push(Keyframe::new(at_ms, color))
+            .map_err(|_| ConvertError::InvalidData)?;
+    }
+    Ok(keyframes)
+}
+
+/// `color` with every channel scaled by `level / 255`
+fn scale(color: RGB8, level: u8) -> RGB8 {
+    let scale = |channel: u8| (channel as u16 * level as u16 / 255) as u8;
+    RGB8::new(scale(color.r), scale(color.g), scale(color.b))
+}
+
+/// Linear blend from `from` (at `t = 0`) to `to` (at `t = span`)
+fn lerp(from: RGB8, to: RGB8, t: u64, span: u64) -> RGB8 {
+    let blend = |from: u8, to: u8| {
+        let (from, to) = (from as i64, to as i64);
+        (from + (to - from) * t as i64 / span as i64) as u8
+    };
+    RGB8::new(
+        blend(from.r, to.r),
+        blend(from.g, to.g),
+        blend(from.b, to.b),
+    )
+}
+
+/// Fully saturated color of `hue` in `0..1536`, red at 0
+fn hue(hue: u64) -> RGB8 {
+    let rising = (hue % 256) as u8;
+    let falling = 255 - rising;
+    match hue / 256 {
+        0 => RGB8::new(255, rising, 0),
+        1 => RGB8::new(falling, 255, 0),
+        2 => RGB8::new(0, 255, rising),
+        3 => RGB8::new(0, falling, 255),
+        4 => RGB8::new(rising, 0, 255),
+        _ => RGB8::new(255, 0, falling),
+    }
+}
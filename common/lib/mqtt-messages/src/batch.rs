@@ -0,0 +1,205 @@
+//! Several timestamped `Telemetry` samples in one message
+//!
+//! Batches are published on `batch_topic(uuid)`, which sits next to the
+//! per-kind topics below `<uuid>/sensor_data/`, so fleet subscriptions to
+//! `+/sensor_data/+` receive them too.
+//!
+//! Binary: enveloped `MessageType::TelemetryBatch`. The body starts with the
+//! timestamp of the earliest sample (u64, ms), followed by one record per
+//! sample: offset from that timestamp (u32, ms), `MessageType` of the sample's
+//! kind (u8) and the binary `Telemetry` payload.
+//!
+//! Timestamps are milliseconds on the board's clock, i.e. since boot unless
+//! the board knows the wall clock time.
+
+use core::fmt::{self, Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::Telemetry;
+#[cfg(feature = "std")]
+use crate::{
+    envelope::{Envelope, MessageType},
+    Codec, ConvertError, TelemetryKind,
+};
+
+/// Last level of `batch_topic`
+pub const BATCH: &str = "batch";
+
+#[cfg(feature = "std")]
+const BASE_LEN: usize = 8;
+#[cfg(feature = "std")]
+const RECORD_HEADER_LEN: usize = 4 + 1;
+
+pub fn write_batch_topic(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/sensor_data/{}", uuid, BATCH)
+}
+
+#[cfg(feature = "std")]
+pub fn batch_topic(uuid: &str) -> String {
+    crate::to_topic(|topic| write_batch_topic(topic, uuid))
+}
+
+/// Whether `topic` is the `batch_topic` of `uuid`
+pub fn is_batch_topic(uuid: &str, topic: &str) -> bool {
+    topic
+        .strip_prefix(uuid)
+        .and_then(|topic| topic.strip_prefix("/sensor_data/"))
+        == Some(BATCH)
+}
+
+/// As JSON: `{"timestamp_ms":1500,"kind":"chip_temperature","value":31.5}`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Sample {
+    pub timestamp_ms: u64,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub telemetry: Telemetry,
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TelemetryBatch {
+    pub samples: Vec<Sample>,
+}
+
+#[cfg(feature = "std")]
+impl TelemetryBatch {
+    pub fn topic(&self, uuid: &str) -> String {
+        batch_topic(uuid)
+    }
+
+    /// Samples may be of different kinds. Batches spanning `u32::MAX` ms
+    /// (about 49 days) or more are `ConvertError::InvalidData`.
+    pub fn encode_with(&self, codec: Codec) -> Result<Vec<u8>, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let base = self
+                    .samples
+                    .iter()
+                    .map(|sample| sample.timestamp_ms)
+                    .min()
+                    .unwrap_or(0);
+                let mut body = base.to_be_bytes().to_vec();
+                for sample in &self.samples {
+                    let offset = u32::try_from(sample.timestamp_ms - base)
+                        .map_err(|_| ConvertError::InvalidData)?;
+                    let kind = sample.telemetry.kind();
+                    body.extend_from_slice(&offset.to_be_bytes());
+                    body.push(MessageType::from(kind) as u8);
+                    body.extend_from_slice(&sample.telemetry.encode());
+                }
+                Envelope::new(MessageType::TelemetryBatch, &body).encode()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::to_vec(self)?),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => Self::decode(data),
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+
+    fn decode(data: &[u8]) -> Result<Self, ConvertError> {
+        let envelope = Envelope::decode(data)?;
+        if envelope.message_type != MessageType::TelemetryBatch {
+            return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+        }
+        let body = envelope.body;
+        if body.len() < BASE_LEN {
+            return Err(ConvertError::Length(body.len()));
+        }
+        let (base, mut records) = body.split_at(BASE_LEN);
+        let base = u64::from_be_bytes(base.try_into().expect("split at BASE_LEN"));
+
+        let mut samples = Vec::new();
+        while !records.is_empty() {
+            if records.len() < RECORD_HEADER_LEN {
+                return Err(ConvertError::Length(body.len()));
+            }
+            let offset = u32::from_be_bytes([records[0], records[1], records[2], records[3]]);
+            let kind = TelemetryKind::try_from(MessageType::try_from(records[4])?)?;
+            let end = RECORD_HEADER_LEN + kind.payload_len();
+            let value = records
+                .get(RECORD_HEADER_LEN..end)
+                .ok_or(ConvertError::Length(body.len()))?;
+            let timestamp_ms = base
+                .checked_add(u64::from(offset))
+                .ok_or(ConvertError::InvalidData)?;
+            samples.push(Sample {
+                timestamp_ms,
+                telemetry: Telemetry::decode(kind, value)?,
+            });
+            records = &records[end..];
+        }
+        Ok(Self { samples })
+    }
+}
+
+/// Collects samples until a batch is full or too old
+///
+/// ```
+/// use mqtt_messages::{batch::Batcher, Telemetry};
+///
+/// let mut batcher = Batcher::new(3, 10_000);
+/// assert!(batcher.push(0, Telemetry::ChipTemperature(30.0)).is_none());
+/// assert!(batcher.push(1_000, Telemetry::StepCount(12)).is_none());
+/// let batch = batcher.push(2_000, Telemetry::ChipTemperature(30.5)).unwrap();
+/// assert_eq!(batch.samples.len(), 3);
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct Batcher {
+    max_samples: usize,
+    max_age_ms: u64,
+    batch: TelemetryBatch,
+}
+
+#[cfg(feature = "std")]
+impl Batcher {
+    pub fn new(max_samples: usize, max_age_ms: u64) -> Self {
+        Self {
+            max_samples: max_samples.max(1),
+            max_age_ms,
+            batch: TelemetryBatch::default(),
+        }
+    }
+
+    /// Adds a sample, returning the batch once it should be published
+    pub fn push(&mut self, timestamp_ms: u64, telemetry: Telemetry) -> Option<TelemetryBatch> {
+        self.batch.samples.push(Sample {
+            timestamp_ms,
+            telemetry,
+        });
+        if self.batch.samples.len() >= self.max_samples {
+            self.flush()
+        } else {
+            self.flush_stale(timestamp_ms)
+        }
+    }
+
+    /// Returns the batch if its first sample is at least `max_age_ms` old
+    pub fn flush_stale(&mut self, now_ms: u64) -> Option<TelemetryBatch> {
+        let first = self.batch.samples.first()?.timestamp_ms;
+        if now_ms.saturating_sub(first) >= self.max_age_ms {
+            self.flush()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the collected samples, unless there are none
+    pub fn flush(&mut self) -> Option<TelemetryBatch> {
+        if self.batch.samples.is_empty() {
+            None
+        } else {
+            Some(core::mem::take(&mut self.batch))
+        }
+    }
+}
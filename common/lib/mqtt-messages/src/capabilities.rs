@@ -0,0 +1,209 @@
+//! What a board can do, announced on `hello_topic`
+//!
+//! Binary: enveloped `MessageType::Capabilities`. Strings are a length byte
+//! followed by UTF-8, lists are a count byte followed by one byte per entry:
+//!
+//! ```text
+//! | firmware version | board revision | codec | commands | sensors | telemetry |
+//! ```
+//!
+//! `codec` is `0` for binary and `1` for JSON, commands are `CommandKind`,
+//! sensors `Sensor` and telemetry the `MessageType` of each `TelemetryKind`.
+//! Boards with older firmware publish an empty hello payload instead.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ConvertError;
+#[cfg(feature = "std")]
+use crate::{
+    envelope::{Envelope, MessageType},
+    Codec, CommandKind, TelemetryKind,
+};
+
+/// Sensors found on ESP32-C3 boards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum Sensor {
+    /// on-chip temperature sensor
+    ChipTemperature = 0,
+    /// temperature and humidity
+    Shtc3 = 1,
+    /// accelerometer, gyroscope and pedometer
+    Icm42670p = 2,
+}
+
+impl Sensor {
+    pub const ALL: [Sensor; 3] = [Sensor::ChipTemperature, Sensor::Shtc3, Sensor::Icm42670p];
+}
+
+impl TryFrom<u8> for Sensor {
+    type Error = ConvertError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Sensor::ALL
+            .into_iter()
+            .find(|sensor| *sensor as u8 == value)
+            .ok_or(ConvertError::InvalidData)
+    }
+}
+
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Capabilities {
+    pub firmware_version: String,
+    pub board_revision: String,
+    /// encoding of all payloads the board publishes and expects
+    pub codec: Codec,
+    /// commands the board acts on
+    pub commands: Vec<CommandKind>,
+    pub sensors: Vec<Sensor>,
+    /// readings the board publishes
+    pub telemetry: Vec<TelemetryKind>,
+}
+
+#[cfg(feature = "std")]
+impl Capabilities {
+    pub fn topic(&self, uuid: &str) -> String {
+        crate::hello_topic(uuid)
+    }
+
+    pub fn supports(&self, command: CommandKind) -> bool {
+        self.commands.contains(&command)
+    }
+
+    pub fn publishes(&self, kind: TelemetryKind) -> bool {
+        self.telemetry.contains(&kind)
+    }
+
+    /// Lists of more than 255 entries are `ConvertError::Length` in binary
+    pub fn encode_with(&self, codec: Codec) -> Result<Vec<u8>, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let mut body = Vec::new();
+                put_str(&mut body, &self.firmware_version);
+                put_str(&mut body, &self.board_revision);
+                body.push(codec_code(self.codec));
+                put_list(&mut body, self.commands.iter().map(|kind| *kind as u8))?;
+                put_list(&mut body, self.sensors.iter().map(|sensor| *sensor as u8))?;
+                put_list(
+                    &mut body,
+                    self.telemetry
+                        .iter()
+                        .map(|kind| MessageType::from(*kind) as u8),
+                )?;
+                Envelope::new(MessageType::Capabilities, &body).encode()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::to_vec(self)?),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let envelope = Envelope::decode(data)?;
+                if envelope.message_type != MessageType::Capabilities {
+                    return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+                }
+                let mut reader = Reader(envelope.body);
+                let capabilities = Capabilities {
+                    firmware_version: reader.string()?,
+                    board_revision: reader.string()?,
+                    codec: codec_from_code(reader.byte()?)?,
+                    commands: reader.list(CommandKind::try_from)?,
+                    sensors: reader.list(Sensor::try_from)?,
+                    telemetry: reader
+                        .list(|code| TelemetryKind::try_from(MessageType::try_from(code)?))?,
+                };
+                if !reader.0.is_empty() {
+                    return Err(ConvertError::Length(envelope.body.len()));
+                }
+                Ok(capabilities)
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn codec_code(codec: Codec) -> u8 {
+    match codec {
+        Codec::Binary => 0,
+        #[cfg(feature = "serde")]
+        Codec::Json => 1,
+    }
+}
+
+#[cfg(feature = "std")]
+fn codec_from_code(code: u8) -> Result<Codec, ConvertError> {
+    match code {
+        0 => Ok(Codec::Binary),
+        #[cfg(feature = "serde")]
+        1 => Ok(Codec::Json),
+        _ => Err(ConvertError::InvalidData),
+    }
+}
+
+/// Strings longer than 255 bytes are cut off
+#[cfg(feature = "std")]
+fn put_str(body: &mut Vec<u8>, s: &str) {
+    let mut len = s.len().min(u8::MAX as usize);
+    while !s.is_char_boundary(len) {
+        len -= 1;
+    }
+    body.push(len as u8);
+    body.extend_from_slice(&s.as_bytes()[..len]);
+}
+
+#[cfg(feature = "std")]
+fn put_list(
+    body: &mut Vec<u8>,
+    items: impl ExactSizeIterator<Item = u8>,
+) -> Result<(), ConvertError> {
+    let len = u8::try_from(items.len()).map_err(|_| ConvertError::Length(items.len()))?;
+    body.push(len);
+    body.extend(items);
+    Ok(())
+}
+
+#[cfg(feature = "std")]
+struct Reader<'a>(&'a [u8]);
+
+#[cfg(feature = "std")]
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], ConvertError> {
+        if self.0.len() < len {
+            return Err(ConvertError::Length(self.0.len()));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn byte(&mut self) -> Result<u8, ConvertError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn string(&mut self) -> Result<String, ConvertError> {
+        let len = self.byte()? as usize;
+        let bytes = self.take(len)?;
+        let s = core::str::from_utf8(bytes).map_err(|_| ConvertError::InvalidData)?;
+        Ok(s.to_owned())
+    }
+
+    fn list<T>(
+        &mut self,
+        item: impl Fn(u8) -> Result<T, ConvertError>,
+    ) -> Result<Vec<T>, ConvertError> {
+        let len = self.byte()? as usize;
+        self.take(len)?.iter().map(|code| item(*code)).collect()
+    }
+}
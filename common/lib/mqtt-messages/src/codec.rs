@@ -0,0 +1,42 @@
+//! Payload encodings
+//!
+//! `Codec::Binary` is the compact big endian format that the board has always
+//! used. With the `serde` feature enabled, `Codec::Json` makes the same
+//! messages readable for dashboards, Node-RED flows and scripts.
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+// variants depend on features, which cargo unifies across dependents
+#[non_exhaustive]
+pub enum Codec {
+    Binary,
+    #[cfg(feature = "serde")]
+    Json,
+}
+
+impl Codec {
+    /// Looks up a codec by the name used in `cfg.toml`: `"binary"` or `"json"`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "binary" => Some(Codec::Binary),
+            #[cfg(feature = "serde")]
+            "json" => Some(Codec::Json),
+            _ => None,
+        }
+    }
+}
+
+// `#[default]` on enum variants is not stable on the firmware toolchain yet
+#[allow(clippy::derivable_impls)]
+impl Default for Codec {
+    fn default() -> Self {
+        Codec::Binary
+    }
+}
@@ -0,0 +1,222 @@
+//! Settings a board can change at runtime
+//!
+//! Hosts read a setting with `Command::ConfigGet` and change it with
+//! `Command::ConfigSet`. The board answers both with the current
+//! `ConfigValue` on `<uuid>/config/<key>`, retained, so hosts that connect
+//! later see the board's settings too.
+//!
+//! Binary: `ConfigGet` is the key (u8), `ConfigSet` the key followed by the
+//! value (u32, big endian). A `ConfigValue` is an enveloped
+//! `MessageType::ConfigValue` with the same layout as `ConfigSet`.
+
+use core::fmt::{self, Write};
+use core::ops::RangeInclusive;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::envelope::{Envelope, MessageType};
+use crate::{Codec, ConvertError};
+
+const VALUE_LEN: usize = 1 + 4;
+
+/// Every setting that can be changed remotely
+///
+/// The numeric codes are part of the wire format and never change, boards
+/// can use them to persist settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum ConfigKey {
+    /// milliseconds between two readings
+    PublishInterval = 0,
+    /// scales every LED color, 255 is full brightness
+    LedBrightness = 1,
+    /// MQTT QoS level of telemetry, 0 to 2
+    MqttQos = 2,
+}
+
+impl ConfigKey {
+    pub const ALL: [ConfigKey; 3] = [
+        ConfigKey::PublishInterval,
+        ConfigKey::LedBrightness,
+        ConfigKey::MqttQos,
+    ];
+
+    /// Last level of the topic
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigKey::PublishInterval => "publish_interval",
+            ConfigKey::LedBrightness => "led_brightness",
+            ConfigKey::MqttQos => "mqtt_qos",
+        }
+    }
+
+    /// Values a board accepts for this setting
+    pub fn range(&self) -> RangeInclusive<u32> {
+        match self {
+            ConfigKey::PublishInterval => 100..=3_600_000,
+            ConfigKey::LedBrightness => 0..=255,
+            ConfigKey::MqttQos => 0..=2,
+        }
+    }
+
+    pub fn write_topic(&self, topic: &mut impl Write, uuid: &str) -> fmt::Result {
+        write!(topic, "{}/config/{}", uuid, self.name())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn topic(&self, uuid: &str) -> String {
+        crate::to_topic(|topic| self.write_topic(topic, uuid))
+    }
+
+    /// Finds the key whose topic (for `uuid`) is `topic`
+    pub fn from_topic(uuid: &str, topic: &str) -> Option<Self> {
+        let name = topic.strip_prefix(uuid)?.strip_prefix("/config/")?;
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+}
+
+impl TryFrom<u8> for ConfigKey {
+    type Error = ConvertError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        ConfigKey::ALL
+            .into_iter()
+            .find(|key| *key as u8 == value)
+            .ok_or(ConvertError::InvalidData)
+    }
+}
+
+/// Matches the values of all of a board's settings
+pub fn write_config_filter(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/config/+", uuid)
+}
+
+#[cfg(feature = "std")]
+pub fn config_filter(uuid: &str) -> String {
+    crate::to_topic(|topic| write_config_filter(topic, uuid))
+}
+
+/// The runtime settings of a board, one field per `ConfigKey`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings {
+    pub publish_interval_ms: u32,
+    pub led_brightness: u8,
+    pub mqtt_qos: u8,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            publish_interval_ms: 1000,
+            led_brightness: u8::MAX,
+            mqtt_qos: 1,
+        }
+    }
+}
+
+impl Settings {
+    pub fn get(&self, key: ConfigKey) -> u32 {
+        match key {
+            ConfigKey::PublishInterval => self.publish_interval_ms,
+            ConfigKey::LedBrightness => self.led_brightness as u32,
+            ConfigKey::MqttQos => self.mqtt_qos as u32,
+        }
+    }
+
+    /// Changes a setting, unless `value` is outside of `key.range()`
+    pub fn set(&mut self, key: ConfigKey, value: u32) -> Result<(), ConvertError> {
+        if !key.range().contains(&value) {
+            return Err(ConvertError::InvalidData);
+        }
+        match key {
+            ConfigKey::PublishInterval => self.publish_interval_ms = value,
+            ConfigKey::LedBrightness => self.led_brightness = value as u8,
+            ConfigKey::MqttQos => self.mqtt_qos = value as u8,
+        }
+        Ok(())
+    }
+
+    pub fn value(&self, key: ConfigKey) -> ConfigValue {
+        ConfigValue {
+            key,
+            value: self.get(key),
+        }
+    }
+}
+
+/// The current value of a setting, as reported by the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ConfigValue {
+    pub key: ConfigKey,
+    pub value: u32,
+}
+
+impl ConfigValue {
+    pub fn write_topic(&self, topic: &mut impl Write, uuid: &str) -> fmt::Result {
+        self.key.write_topic(topic, uuid)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn topic(&self, uuid: &str) -> String {
+        self.key.topic(uuid)
+    }
+
+    /// Writes the enveloped payload to `buf`, returning the number of bytes used
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, ConvertError> {
+        Envelope::new(MessageType::ConfigValue, &self.body()).encode_into(buf)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode_with(&self, codec: Codec) -> Vec<u8> {
+        match codec {
+            Codec::Binary => {
+                let mut data = [0; crate::envelope::HEADER_LEN + VALUE_LEN];
+                let len = self
+                    .encode_into(&mut data)
+                    .expect("buffer has room for a value");
+                data[..len].to_vec()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => serde_json::to_vec(self).expect("values always serialize"),
+        }
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, ConvertError> {
+        let envelope = Envelope::decode(data)?;
+        if envelope.message_type != MessageType::ConfigValue {
+            return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+        }
+        let (key, value) = decode_body(envelope.body)?;
+        Ok(Self { key, value })
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => Self::decode(data),
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+
+    pub(crate) fn body(&self) -> [u8; VALUE_LEN] {
+        let value = self.value.to_be_bytes();
+        [self.key as u8, value[0], value[1], value[2], value[3]]
+    }
+}
+
+/// Splits a `ConfigSet` or `ConfigValue` body into key and value
+pub(crate) fn decode_body(body: &[u8]) -> Result<(ConfigKey, u32), ConvertError> {
+    let body: [u8; VALUE_LEN] = body
+        .try_into()
+        .map_err(|_| ConvertError::Length(body.len()))?;
+    let key = ConfigKey::try_from(body[0])?;
+    let value = u32::from_be_bytes([body[1], body[2], body[3], body[4]]);
+    Ok((key, value))
+}
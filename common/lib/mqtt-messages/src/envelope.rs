@@ -0,0 +1,215 @@
+//! Optional versioned wrapper around message payloads
+//!
+//! A wrapped payload starts with a four byte header:
+//!
+//! ```text
+//! | magic/version | type | length (u16, big endian) | body ... |
+//! ```
+//!
+//! The upper nibble of the first byte is always `MAGIC`, the lower nibble is
+//! the envelope version. Payloads without a header are "legacy v0" and are
+//! recognized by their fixed size, e.g. the 3 byte `ColorData` format.
+
+use crate::{ConvertError, TelemetryKind};
+
+pub const MAGIC: u8 = 0xE0;
+const MAGIC_MASK: u8 = 0xF0;
+
+/// Version written by `Envelope::new`
+pub const VERSION: u8 = 1;
+
+pub const HEADER_LEN: usize = 4;
+
+/// Longest body the header's length field can describe
+pub const MAX_BODY_LEN: usize = u16::MAX as usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MessageType {
+    Color = 0x01,
+    Command = 0x02,
+    CommandRequest = 0x03,
+    Ack = 0x04,
+    Status = 0x05,
+    Signed = 0x06,
+    Sealed = 0x07,
+    Capabilities = 0x08,
+    ConfigValue = 0x09,
+    OtaStart = 0x0A,
+    OtaReport = 0x0B,
+    ChipTemperature = 0x10,
+    AmbientTemperature = 0x11,
+    Humidity = 0x12,
+    Accel = 0x13,
+    Gyro = 0x14,
+    StepCount = 0x15,
+    TelemetryBatch = 0x20,
+}
+
+impl TryFrom<u8> for MessageType {
+    type Error = ConvertError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let message_type = match value {
+            0x01 => MessageType::Color,
+            0x02 => MessageType::Command,
+            0x03 => MessageType::CommandRequest,
+            0x04 => MessageType::Ack,
+            0x05 => MessageType::Status,
+            0x06 => MessageType::Signed,
+            0x07 => MessageType::Sealed,
+            0x08 => MessageType::Capabilities,
+            0x09 => MessageType::ConfigValue,
+            0x0A => MessageType::OtaStart,
+            0x0B => MessageType::OtaReport,
+            0x10 => MessageType::ChipTemperature,
+            0x11 => MessageType::AmbientTemperature,
+            0x12 => MessageType::Humidity,
+            0x13 => MessageType::Accel,
+            0x14 => MessageType::Gyro,
+            0x15 => MessageType::StepCount,
+            0x20 => MessageType::TelemetryBatch,
+            unknown => return Err(ConvertError::UnknownMessageType(unknown)),
+        };
+        Ok(message_type)
+    }
+}
+
+impl From<TelemetryKind> for MessageType {
+    fn from(kind: TelemetryKind) -> Self {
+        match kind {
+            TelemetryKind::ChipTemperature => MessageType::ChipTemperature,
+            TelemetryKind::AmbientTemperature => MessageType::AmbientTemperature,
+            TelemetryKind::Humidity => MessageType::Humidity,
+            TelemetryKind::Accel => MessageType::Accel,
+            TelemetryKind::Gyro => MessageType::Gyro,
+            TelemetryKind::StepCount => MessageType::StepCount,
+        }
+    }
+}
+
+impl TryFrom<MessageType> for TelemetryKind {
+    type Error = ConvertError;
+
+    fn try_from(message_type: MessageType) -> Result<Self, Self::Error> {
+        match message_type {
+            MessageType::ChipTemperature => Ok(TelemetryKind::ChipTemperature),
+            MessageType::AmbientTemperature => Ok(TelemetryKind::AmbientTemperature),
+            MessageType::Humidity => Ok(TelemetryKind::Humidity),
+            MessageType::Accel => Ok(TelemetryKind::Accel),
+            MessageType::Gyro => Ok(TelemetryKind::Gyro),
+            MessageType::StepCount => Ok(TelemetryKind::StepCount),
+            other => Err(ConvertError::UnexpectedMessageType(other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Envelope<'a> {
+    /// `0` for legacy payloads that were received without a header
+    pub version: u8,
+    pub message_type: MessageType,
+    pub body: &'a [u8],
+}
+
+impl<'a> Envelope<'a> {
+    pub fn new(message_type: MessageType, body: &'a [u8]) -> Self {
+        Self {
+            version: VERSION,
+            message_type,
+            body,
+        }
+    }
+
+    /// Number of bytes `encode_into` needs
+    pub fn encoded_len(&self) -> usize {
+        HEADER_LEN + self.body.len()
+    }
+
+    /// Writes header and body to `buf`, always using the current `VERSION`
+    ///
+    /// Returns the number of bytes used. Bodies longer than `MAX_BODY_LEN`
+    /// are `ConvertError::Length`.
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, ConvertError> {
+        let header = Self::header(self.message_type, self.body.len())?;
+        let encoded_len = self.encoded_len();
+        let buf = buf
+            .get_mut(..encoded_len)
+            .ok_or(ConvertError::BufferTooSmall(encoded_len))?;
+        buf[..HEADER_LEN].copy_from_slice(&header);
+        buf[HEADER_LEN..].copy_from_slice(self.body);
+        Ok(encoded_len)
+    }
+
+    /// The header for a body of `body_len` bytes, for bodies that are
+    /// written in place behind it
+    pub fn header(
+        message_type: MessageType,
+        body_len: usize,
+    ) -> Result<[u8; HEADER_LEN], ConvertError> {
+        let len = u16::try_from(body_len)
+            .map_err(|_| ConvertError::Length(body_len))?
+            .to_be_bytes();
+        Ok([MAGIC | VERSION, message_type as u8, len[0], len[1]])
+    }
+
+    /// Serializes header and body, always using the current `VERSION`
+    #[cfg(feature = "std")]
+    pub fn encode(&self) -> Result<Vec<u8>, ConvertError> {
+        let mut data = vec![0; self.encoded_len()];
+        self.encode_into(&mut data)?;
+        Ok(data)
+    }
+
+    /// Parses a payload that must carry a header
+    pub fn decode(data: &'a [u8]) -> Result<Self, ConvertError> {
+        if data.len() < HEADER_LEN {
+            return Err(ConvertError::Length(data.len()));
+        }
+        if data[0] & MAGIC_MASK != MAGIC {
+            return Err(ConvertError::InvalidHeader);
+        }
+
+        let version = data[0] & !MAGIC_MASK;
+        if version == 0 || version > VERSION {
+            return Err(ConvertError::UnsupportedVersion(version));
+        }
+
+        let message_type = MessageType::try_from(data[1])?;
+        let len = u16::from_be_bytes([data[2], data[3]]) as usize;
+        let body = &data[HEADER_LEN..];
+        if body.len() != len {
+            return Err(ConvertError::Length(body.len()));
+        }
+
+        Ok(Self {
+            version,
+            message_type,
+            body,
+        })
+    }
+
+    /// Parses a payload of `message_type` that is either wrapped or legacy v0
+    ///
+    /// A payload of exactly `legacy_len` bytes is taken as legacy v0,
+    /// anything else has to be a wrapped message of the expected type.
+    pub fn decode_or_legacy(
+        data: &'a [u8],
+        message_type: MessageType,
+        legacy_len: usize,
+    ) -> Result<Self, ConvertError> {
+        if data.len() == legacy_len {
+            return Ok(Self {
+                version: 0,
+                message_type,
+                body: data,
+            });
+        }
+
+        let envelope = Self::decode(data)?;
+        if envelope.message_type != message_type {
+            return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+        }
+        Ok(envelope)
+    }
+}
@@ -0,0 +1,168 @@
+//! Home Assistant MQTT discovery
+//!
+//! Publishing these configs (retained) makes a board show up in Home Assistant
+//! as one device with a temperature sensor and an RGB light, without writing
+//! any YAML. Home Assistant can only read JSON payloads, so the board has to
+//! use `Codec::Json`.
+//!
+//! See <https://www.home-assistant.io/integrations/mqtt/#mqtt-discovery>
+
+use serde::Serialize;
+
+use crate::{color_topic, status_topic, TelemetryKind, TopicScheme};
+
+/// Home Assistant's default `discovery_prefix`
+pub const DISCOVERY_PREFIX: &str = "homeassistant";
+
+const MANUFACTURER: &str = "Espressif";
+const MODEL: &str = "ESP32-C3-DevKitC-02";
+
+/// Object ID of the board LED, also used in its `unique_id`
+const BOARD_LED: &str = "board_led";
+
+/// Turns the light on with the color picked in Home Assistant, as `ColorData`
+const LIGHT_ON_TEMPLATE: &str = concat!(
+    r#"{"board_led":{"#,
+    r#""r":{{ red | default(255) }},"#,
+    r#""g":{{ green | default(255) }},"#,
+    r#""b":{{ blue | default(255) }}}}"#,
+);
+const LIGHT_OFF_TEMPLATE: &str = r#"{"board_led":{"r":0,"g":0,"b":0}}"#;
+
+/// A discovery config and the topic it has to be published on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discovery {
+    pub topic: String,
+    pub payload: Vec<u8>,
+}
+
+/// All entities of the board with `uuid`
+///
+/// Entity IDs only use the UUID, while the board's topics follow `scheme`.
+pub fn discoveries(scheme: &TopicScheme, uuid: &str) -> Vec<Discovery> {
+    vec![
+        temperature_sensor(scheme, uuid),
+        board_led_light(scheme, uuid),
+    ]
+}
+
+/// The on-chip temperature sensor, reading `temperature_data_topic`
+pub fn temperature_sensor(scheme: &TopicScheme, uuid: &str) -> Discovery {
+    let kind = TelemetryKind::ChipTemperature;
+    let root = scheme.root(uuid);
+    let config = SensorConfig {
+        name: "Board temperature",
+        unique_id: unique_id(uuid, kind.name()),
+        device: Device::new(uuid),
+        availability: Availability::new(&root),
+        state_topic: kind.topic(&root),
+        value_template: "{{ value_json.value }}",
+        device_class: "temperature",
+        state_class: "measurement",
+        unit_of_measurement: "°C",
+    };
+    Discovery::new("sensor", uuid, kind.name(), &config)
+}
+
+/// The board LED, driven through `color_topic`
+///
+/// The board does not report its color, so Home Assistant tracks the state
+/// optimistically.
+pub fn board_led_light(scheme: &TopicScheme, uuid: &str) -> Discovery {
+    let root = scheme.root(uuid);
+    let config = LightConfig {
+        name: "Board LED",
+        unique_id: unique_id(uuid, BOARD_LED),
+        device: Device::new(uuid),
+        availability: Availability::new(&root),
+        schema: "template",
+        command_topic: color_topic(&root),
+        command_on_template: LIGHT_ON_TEMPLATE,
+        command_off_template: LIGHT_OFF_TEMPLATE,
+        optimistic: true,
+    };
+    Discovery::new("light", uuid, BOARD_LED, &config)
+}
+
+/// `homeassistant/<component>/<uuid>/<object_id>/config`
+pub fn discovery_topic(component: &str, uuid: &str, object_id: &str) -> String {
+    format!(
+        "{}/{}/{}/{}/config",
+        DISCOVERY_PREFIX, component, uuid, object_id
+    )
+}
+
+impl Discovery {
+    fn new(component: &str, uuid: &str, object_id: &str, config: &impl Serialize) -> Self {
+        Self {
+            topic: discovery_topic(component, uuid, object_id),
+            payload: serde_json::to_vec(config).expect("discovery configs always serialize"),
+        }
+    }
+}
+
+fn unique_id(uuid: &str, object_id: &str) -> String {
+    format!("{}_{}", uuid, object_id)
+}
+
+/// Groups all entities of one board
+#[derive(Serialize)]
+struct Device<'a> {
+    identifiers: [&'a str; 1],
+    name: String,
+    manufacturer: &'static str,
+    model: &'static str,
+}
+
+impl<'a> Device<'a> {
+    fn new(uuid: &'a str) -> Self {
+        Self {
+            identifiers: [uuid],
+            name: format!("ESP32-C3 {}", uuid),
+            manufacturer: MANUFACTURER,
+            model: MODEL,
+        }
+    }
+}
+
+/// Entities go unavailable when the board's `Status` turns `offline`
+#[derive(Serialize)]
+struct Availability {
+    topic: String,
+    value_template: &'static str,
+}
+
+impl Availability {
+    fn new(root: &str) -> [Self; 1] {
+        [Self {
+            topic: status_topic(root),
+            value_template: "{{ value_json.state }}",
+        }]
+    }
+}
+
+#[derive(Serialize)]
+struct SensorConfig<'a> {
+    name: &'static str,
+    unique_id: String,
+    device: Device<'a>,
+    availability: [Availability; 1],
+    state_topic: String,
+    value_template: &'static str,
+    device_class: &'static str,
+    state_class: &'static str,
+    unit_of_measurement: &'static str,
+}
+
+#[derive(Serialize)]
+struct LightConfig<'a> {
+    name: &'static str,
+    unique_id: String,
+    device: Device<'a>,
+    availability: [Availability; 1],
+    schema: &'static str,
+    command_topic: String,
+    command_on_template: &'static str,
+    command_off_template: &'static str,
+    optimistic: bool,
+}
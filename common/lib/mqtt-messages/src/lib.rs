@@ -1,107 +1,763 @@
-use rgb::ComponentSlice;
+//! Topics and payloads shared by the board firmware and host tools
+//!
+//! With the default `std` feature, topic builders return a `String` and
+//! encoders return a `Vec<u8>`. Without it the crate is `no_std` and does not
+//! allocate: use the `write_*` topic builders with any `core::fmt::Write`
+//! (e.g. `TopicString`) and the `*_into` encoders with a `&mut [u8]`.
+//!
+//! Topic builders and parsers that take a `uuid` work below a board's root,
+//! which is the UUID itself unless a `TopicScheme` adds a prefix. In that case
+//! pass `scheme.root(uuid)` instead, see `scheme`.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use core::fmt::{self, Write};
+pub use heapless;
 pub use rgb::RGB8;
+#[cfg(feature = "std")]
 use std::borrow::{Borrow, Cow};
-use std::str;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+pub mod ack;
+pub mod animation;
+pub mod batch;
+pub mod capabilities;
+pub mod codec;
+pub mod config;
+pub mod envelope;
+#[cfg(feature = "serde")]
+pub mod home_assistant;
+pub mod ota;
+pub mod scheme;
+#[cfg(feature = "sealing")]
+pub mod sealing;
+#[cfg(feature = "signing")]
+pub mod signing;
+pub mod status;
+pub mod target;
+pub mod topic_filter;
+
+#[cfg(feature = "std")]
+pub use ack::Ack;
+pub use ack::{AckStatus, CommandRequest};
+pub use animation::{Animation, Keyframe, Pattern};
+#[cfg(feature = "std")]
+pub use batch::{batch_topic, Batcher, TelemetryBatch};
+pub use batch::{is_batch_topic, write_batch_topic, Sample};
+#[cfg(feature = "std")]
+pub use capabilities::Capabilities;
+pub use capabilities::Sensor;
+pub use codec::Codec;
+pub use config::{ConfigKey, ConfigValue, Settings};
+use envelope::{Envelope, MessageType};
+#[cfg(feature = "std")]
+pub use ota::{OtaReport, OtaStart};
+pub use scheme::TopicScheme;
+#[cfg(feature = "std")]
+pub use status::{status_topic, Heartbeat, Status};
+pub use status::{write_status_topic, ResetReason};
+pub use target::Target;
+pub use topic_filter::TopicFilter;
+
+/// Long enough for every topic built by this crate
+pub const TOPIC_CAPACITY: usize = 128;
+
+/// A stack allocated topic for the `write_*` builders
+pub type TopicString = heapless::String<TOPIC_CAPACITY>;
+
+/// Largest binary `Telemetry` payload in bytes
+pub const MAX_TELEMETRY_LEN: usize = 12;
+
+/// Longest group name, see `target::is_group_name`
+pub const GROUP_NAME_CAPACITY: usize = 32;
+
+pub type GroupName = heapless::String<GROUP_NAME_CAPACITY>;
+
+/// Largest binary `Command` payload in bytes, that of an `Animation`
+pub const MAX_COMMAND_LEN: usize = animation::MAX_LEN;
+
+pub type CommandData = heapless::Vec<u8, MAX_COMMAND_LEN>;
+
+#[cfg(feature = "std")]
+fn to_topic(write_topic: impl FnOnce(&mut String) -> fmt::Result) -> String {
+    let mut topic = String::new();
+    write_topic(&mut topic).expect("writing to a String cannot fail");
+    topic
+}
 
 /// Handles `EspMqttMessage` with MQTT hierarchy
 ///
 /// Can be used to send ColorData(rgb) with `Command` in a hierarchical context
+pub fn write_cmd_topic_fragment(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    Target::Device(uuid).write_cmd_topic_fragment(topic)
+}
 
+#[cfg(feature = "std")]
 pub fn cmd_topic_fragment(uuid: &str) -> String {
-    format!("{}/command/", uuid)
+    to_topic(|topic| write_cmd_topic_fragment(topic, uuid))
 }
 
 /// Handles `EspMqttMessage` without MQTT hierarchy
 ///
 /// Used to send ColorData(rgb)
+pub fn write_color_topic(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/color_topic", uuid)
+}
+
+#[cfg(feature = "std")]
 pub fn color_topic(uuid: &str) -> String {
-    format!("{}/color_topic", uuid)
+    to_topic(|topic| write_color_topic(topic, uuid))
 }
 
+/// On-chip temperature sensor, see `Telemetry::ChipTemperature`
+#[cfg(feature = "std")]
 pub fn temperature_data_topic(uuid: &str) -> String {
-    format!("{}/sensor_data/temperature", uuid)
+    TelemetryKind::ChipTemperature.topic(uuid)
+}
+
+/// SHTC3 temperature, see `Telemetry::AmbientTemperature`
+#[cfg(feature = "std")]
+pub fn ambient_temperature_data_topic(uuid: &str) -> String {
+    TelemetryKind::AmbientTemperature.topic(uuid)
+}
+
+/// SHTC3 relative humidity, see `Telemetry::Humidity`
+#[cfg(feature = "std")]
+pub fn humidity_data_topic(uuid: &str) -> String {
+    TelemetryKind::Humidity.topic(uuid)
+}
+
+/// ICM-42670-P accelerometer, see `Telemetry::Accel`
+#[cfg(feature = "std")]
+pub fn accel_data_topic(uuid: &str) -> String {
+    TelemetryKind::Accel.topic(uuid)
+}
+
+/// ICM-42670-P gyroscope, see `Telemetry::Gyro`
+#[cfg(feature = "std")]
+pub fn gyro_data_topic(uuid: &str) -> String {
+    TelemetryKind::Gyro.topic(uuid)
 }
 
+/// ICM-42670-P pedometer, see `Telemetry::StepCount`
+#[cfg(feature = "std")]
+pub fn step_count_data_topic(uuid: &str) -> String {
+    TelemetryKind::StepCount.topic(uuid)
+}
+
+pub fn write_hello_topic(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/hello", uuid)
+}
+
+#[cfg(feature = "std")]
 pub fn hello_topic(uuid: &str) -> String {
-    format!("{}/hello", uuid)
+    to_topic(|topic| write_hello_topic(topic, uuid))
 }
 
+/// Binary payloads: `BoardLed` is r, g, b, the group commands carry the
+/// group name as ASCII.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum Command {
     BoardLed(RGB8),
+    /// start listening to `group/<name>/command/...`
+    JoinGroup(GroupName),
+    LeaveGroup(GroupName),
+    /// ask for the current value of a setting
+    ConfigGet {
+        key: ConfigKey,
+    },
+    /// change a setting at runtime
+    ConfigSet {
+        key: ConfigKey,
+        value: u32,
+    },
+    /// play an LED pattern on the board
+    Animation(Animation),
 }
 
 impl Command {
     const BOARD_LED: &'static str = "board_led";
 
+    pub fn kind(&self) -> CommandKind {
+        match self {
+            Command::BoardLed(_) => CommandKind::BoardLed,
+            Command::JoinGroup(_) => CommandKind::JoinGroup,
+            Command::LeaveGroup(_) => CommandKind::LeaveGroup,
+            Command::ConfigGet { .. } => CommandKind::ConfigGet,
+            Command::ConfigSet { .. } => CommandKind::ConfigSet,
+            Command::Animation(_) => CommandKind::Animation,
+        }
+    }
+
+    pub fn write_topic(&self, topic: &mut impl Write, uuid: &str) -> fmt::Result {
+        self.write_topic_to(topic, &TopicScheme::BARE, Target::Device(uuid))
+    }
+
+    #[cfg(feature = "std")]
     pub fn topic(&self, uuid: &str) -> String {
+        to_topic(|topic| self.write_topic(topic, uuid))
+    }
+
+    /// The topic of this command for a single board, a group or everyone
+    pub fn write_topic_to(
+        &self,
+        topic: &mut impl Write,
+        scheme: &TopicScheme,
+        target: Target,
+    ) -> fmt::Result {
+        scheme.write_cmd_topic_fragment(topic, target)?;
+        topic.write_str(self.kind().name())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn topic_to(&self, scheme: &TopicScheme, target: Target) -> String {
+        to_topic(|topic| self.write_topic_to(topic, scheme, target))
+    }
+
+    /// The binary payload, without envelope
+    pub fn data(&self) -> CommandData {
+        let mut data = CommandData::new();
+        let fits = match self {
+            Command::BoardLed(led_data) => data.extend_from_slice(led_data.as_ref()),
+            Command::JoinGroup(name) | Command::LeaveGroup(name) => {
+                data.extend_from_slice(name.as_bytes())
+            }
+            Command::ConfigGet { key } => data.extend_from_slice(&[*key as u8]),
+            Command::ConfigSet { key, value } => {
+                let value = ConfigValue {
+                    key: *key,
+                    value: *value,
+                };
+                data.extend_from_slice(&value.body())
+            }
+            Command::Animation(animation) => {
+                let mut buf = [0; animation::MAX_LEN];
+                let len = animation
+                    .encode_into(&mut buf)
+                    .expect("animations have at most MAX_KEYFRAMES");
+                data.extend_from_slice(&buf[..len])
+            }
+        };
+        fits.expect("every command fits MAX_COMMAND_LEN");
+        data
+    }
+
+    /// Decodes a bare or enveloped binary `BoardLed` payload
+    ///
+    /// Payloads of other commands can only be told apart by their topic, see
+    /// `decode_kind`.
+    pub fn decode(data: &[u8]) -> Result<Self, ConvertError> {
+        Self::decode_kind(CommandKind::BoardLed, data)
+    }
+
+    /// Decodes a bare or enveloped binary payload of a `kind` command
+    pub fn decode_kind(kind: CommandKind, data: &[u8]) -> Result<Self, ConvertError> {
+        match kind {
+            CommandKind::BoardLed => {
+                let data = Envelope::decode_or_legacy(data, MessageType::Command, 3)?.body;
+                let data: [u8; 3] = data
+                    .try_into()
+                    .map_err(|_| ConvertError::Length(data.len()))?;
+                let rgb = RGB8::new(data[0], data[1], data[2]);
+                Ok(Command::BoardLed(rgb))
+            }
+            CommandKind::JoinGroup | CommandKind::LeaveGroup => {
+                // group names are ASCII, so they never start with `envelope::MAGIC`
+                let data = match Envelope::decode(data) {
+                    Ok(envelope) if envelope.message_type == MessageType::Command => envelope.body,
+                    _ => data,
+                };
+                let name = core::str::from_utf8(data).map_err(|_| ConvertError::InvalidData)?;
+                let name = target::group_name(name)?;
+                Ok(match kind {
+                    CommandKind::JoinGroup => Command::JoinGroup(name),
+                    _ => Command::LeaveGroup(name),
+                })
+            }
+            CommandKind::ConfigGet => {
+                let data = Envelope::decode_or_legacy(data, MessageType::Command, 1)?.body;
+                let key = match data {
+                    [key] => ConfigKey::try_from(*key)?,
+                    _ => return Err(ConvertError::Length(data.len())),
+                };
+                Ok(Command::ConfigGet { key })
+            }
+            CommandKind::ConfigSet => {
+                let data = Envelope::decode_or_legacy(data, MessageType::Command, 5)?.body;
+                let (key, value) = config::decode_body(data)?;
+                Ok(Command::ConfigSet { key, value })
+            }
+            CommandKind::Animation => {
+                // animations start with their pattern, never with `envelope::MAGIC`
+                let data = match Envelope::decode(data) {
+                    Ok(envelope) if envelope.message_type == MessageType::Command => envelope.body,
+                    _ => data,
+                };
+                Ok(Command::Animation(Animation::decode(data)?))
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode_with(&self, codec: Codec) -> Vec<u8> {
+        match codec {
+            Codec::Binary => self.data().to_vec(),
+            #[cfg(feature = "serde")]
+            Codec::Json => serde_json::to_vec(self).expect("commands always serialize"),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        Self::decode_kind_with(codec, CommandKind::BoardLed, data)
+    }
+
+    /// Decodes a payload received on the topic of a `kind` command
+    pub fn decode_kind_with(
+        codec: Codec,
+        kind: CommandKind,
+        data: &[u8],
+    ) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => Self::decode_kind(kind, data),
+            #[cfg(feature = "serde")]
+            Codec::Json => {
+                let command: Command = serde_json::from_slice(data)?;
+                if command.kind() != kind {
+                    return Err(ConvertError::InvalidPath);
+                }
+                command.validate()?;
+                Ok(command)
+            }
+        }
+    }
+
+    /// Rejects commands that serde accepts but the binary decoder does not
+    #[cfg(feature = "serde")]
+    pub(crate) fn validate(&self) -> Result<(), ConvertError> {
         match self {
-            Command::BoardLed(_) => {
-                format!("{}{}", cmd_topic_fragment(uuid), Self::BOARD_LED)
+            Command::JoinGroup(name) | Command::LeaveGroup(name) => {
+                target::group_name(name).map(|_| ())
             }
+            Command::Animation(animation) => animation.validate(),
+            _ => Ok(()),
         }
     }
+}
 
-    pub fn data(&self) -> &[u8] {
+/// The variants of `Command`, without their data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum CommandKind {
+    BoardLed = 0,
+    JoinGroup = 1,
+    LeaveGroup = 2,
+    ConfigGet = 3,
+    ConfigSet = 4,
+    Animation = 5,
+}
+
+impl CommandKind {
+    pub const ALL: [CommandKind; 6] = [
+        CommandKind::BoardLed,
+        CommandKind::JoinGroup,
+        CommandKind::LeaveGroup,
+        CommandKind::ConfigGet,
+        CommandKind::ConfigSet,
+        CommandKind::Animation,
+    ];
+
+    /// Last level of the command topic
+    pub fn name(&self) -> &'static str {
         match self {
-            Command::BoardLed(led_data) => led_data.as_slice(),
+            CommandKind::BoardLed => Command::BOARD_LED,
+            CommandKind::JoinGroup => "join_group",
+            CommandKind::LeaveGroup => "leave_group",
+            CommandKind::ConfigGet => "config_get",
+            CommandKind::ConfigSet => "config_set",
+            CommandKind::Animation => "animation",
         }
     }
 }
 
-/// `ColorData` is a simplified `Command`
+impl TryFrom<u8> for CommandKind {
+    type Error = ConvertError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        CommandKind::ALL
+            .into_iter()
+            .find(|kind| *kind as u8 == value)
+            .ok_or(ConvertError::InvalidData)
+    }
+}
 
+/// `ColorData` is a simplified `Command`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum ColorData {
     BoardLed(RGB8),
 }
 impl ColorData {
-    pub fn topic(&self, uuid: &str) -> String {
+    pub fn write_topic(&self, topic: &mut impl Write, uuid: &str) -> fmt::Result {
         match self {
-            ColorData::BoardLed(_) => color_topic(uuid),
+            ColorData::BoardLed(_) => write_color_topic(topic, uuid),
         }
     }
+    #[cfg(feature = "std")]
+    pub fn topic(&self, uuid: &str) -> String {
+        to_topic(|topic| self.write_topic(topic, uuid))
+    }
     pub fn data(&self) -> &[u8] {
         match self {
-            ColorData::BoardLed(led_data) => led_data.as_slice(),
+            ColorData::BoardLed(led_data) => led_data.as_ref(),
         }
     }
+
+    /// `data()` wrapped in a versioned `Envelope`
+    #[cfg(feature = "std")]
+    pub fn enveloped(&self) -> Vec<u8> {
+        Envelope::new(MessageType::Color, self.data())
+            .encode()
+            .expect("colors fit an envelope")
+    }
+
+    /// Writes `data()` wrapped in a versioned `Envelope` to `buf`,
+    /// returning the number of bytes used
+    pub fn enveloped_into(&self, buf: &mut [u8]) -> Result<usize, ConvertError> {
+        Envelope::new(MessageType::Color, self.data()).encode_into(buf)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode_with(&self, codec: Codec) -> Vec<u8> {
+        match codec {
+            Codec::Binary => self.data().to_vec(),
+            #[cfg(feature = "serde")]
+            Codec::Json => serde_json::to_vec(self).expect("colors always serialize"),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => ColorData::try_from(data),
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
+
+/// A three-axis reading, as delivered by the ICM-42670-P
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Vector3 {
+    pub fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+}
+
+/// Which sensor a `Telemetry` value comes from
+///
+/// Every kind has its own topic below `<uuid>/sensor_data/`, so the kind of
+/// an incoming message can be recovered from its topic alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum TelemetryKind {
+    ChipTemperature,
+    AmbientTemperature,
+    Humidity,
+    Accel,
+    Gyro,
+    StepCount,
+}
+
+impl TelemetryKind {
+    pub const ALL: [TelemetryKind; 6] = [
+        TelemetryKind::ChipTemperature,
+        TelemetryKind::AmbientTemperature,
+        TelemetryKind::Humidity,
+        TelemetryKind::Accel,
+        TelemetryKind::Gyro,
+        TelemetryKind::StepCount,
+    ];
+
+    /// Last level of the topic
+    pub fn name(&self) -> &'static str {
+        match self {
+            TelemetryKind::ChipTemperature => "temperature",
+            TelemetryKind::AmbientTemperature => "ambient_temperature",
+            TelemetryKind::Humidity => "humidity",
+            TelemetryKind::Accel => "accel",
+            TelemetryKind::Gyro => "gyro",
+            TelemetryKind::StepCount => "step_count",
+        }
+    }
+
+    pub fn write_topic(&self, topic: &mut impl Write, uuid: &str) -> fmt::Result {
+        write!(topic, "{}/sensor_data/{}", uuid, self.name())
+    }
+
+    #[cfg(feature = "std")]
+    pub fn topic(&self, uuid: &str) -> String {
+        to_topic(|topic| self.write_topic(topic, uuid))
+    }
+
+    /// Finds the kind whose topic (for `uuid`) is `topic`
+    pub fn from_topic(uuid: &str, topic: &str) -> Option<Self> {
+        let name = topic.strip_prefix(uuid)?.strip_prefix("/sensor_data/")?;
+        Self::ALL.into_iter().find(|kind| kind.name() == name)
+    }
+
+    /// Size of the binary payload in bytes
+    pub fn payload_len(&self) -> usize {
+        match self {
+            TelemetryKind::ChipTemperature
+            | TelemetryKind::AmbientTemperature
+            | TelemetryKind::Humidity
+            | TelemetryKind::StepCount => 4,
+            TelemetryKind::Accel | TelemetryKind::Gyro => 12,
+        }
+    }
+}
+
+/// Sensor readings published by the board
+///
+/// All values are encoded big endian, floats as `f32::to_be_bytes`.
+/// A `Vector3` is encoded as x, y, z.
+/// As JSON, a reading looks like `{"kind":"humidity","value":45.2}`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "kind", content = "value", rename_all = "snake_case")
+)]
+pub enum Telemetry {
+    /// on-chip temperature sensor, °C
+    ChipTemperature(f32),
+    /// SHTC3 temperature, °C
+    AmbientTemperature(f32),
+    /// SHTC3 relative humidity, %
+    Humidity(f32),
+    /// ICM-42670-P acceleration, g
+    Accel(Vector3),
+    /// ICM-42670-P angular rate, °/s
+    Gyro(Vector3),
+    /// ICM-42670-P pedometer
+    StepCount(u32),
+}
+
+impl Telemetry {
+    pub fn kind(&self) -> TelemetryKind {
+        match self {
+            Telemetry::ChipTemperature(_) => TelemetryKind::ChipTemperature,
+            Telemetry::AmbientTemperature(_) => TelemetryKind::AmbientTemperature,
+            Telemetry::Humidity(_) => TelemetryKind::Humidity,
+            Telemetry::Accel(_) => TelemetryKind::Accel,
+            Telemetry::Gyro(_) => TelemetryKind::Gyro,
+            Telemetry::StepCount(_) => TelemetryKind::StepCount,
+        }
+    }
+
+    pub fn write_topic(&self, topic: &mut impl Write, uuid: &str) -> fmt::Result {
+        self.kind().write_topic(topic, uuid)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn topic(&self, uuid: &str) -> String {
+        self.kind().topic(uuid)
+    }
+
+    /// Writes the binary payload to `buf`, returning the number of bytes used
+    pub fn encode_into(&self, buf: &mut [u8]) -> Result<usize, ConvertError> {
+        let len = self.kind().payload_len();
+        let buf = buf
+            .get_mut(..len)
+            .ok_or(ConvertError::BufferTooSmall(len))?;
+        match self {
+            Telemetry::ChipTemperature(value)
+            | Telemetry::AmbientTemperature(value)
+            | Telemetry::Humidity(value) => buf.copy_from_slice(&value.to_be_bytes()),
+            Telemetry::Accel(vector) | Telemetry::Gyro(vector) => {
+                buf[0..4].copy_from_slice(&vector.x.to_be_bytes());
+                buf[4..8].copy_from_slice(&vector.y.to_be_bytes());
+                buf[8..12].copy_from_slice(&vector.z.to_be_bytes());
+            }
+            Telemetry::StepCount(steps) => buf.copy_from_slice(&steps.to_be_bytes()),
+        }
+        Ok(len)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = [0; MAX_TELEMETRY_LEN];
+        let len = self
+            .encode_into(&mut data)
+            .expect("telemetry fits MAX_TELEMETRY_LEN");
+        data[..len].to_vec()
+    }
+
+    /// `encode()` wrapped in a versioned `Envelope`
+    #[cfg(feature = "std")]
+    pub fn enveloped(&self) -> Vec<u8> {
+        Envelope::new(self.kind().into(), &self.encode())
+            .encode()
+            .expect("telemetry fits an envelope")
+    }
+
+    /// Writes `encode()` wrapped in a versioned `Envelope` to `buf`,
+    /// returning the number of bytes used
+    pub fn enveloped_into(&self, buf: &mut [u8]) -> Result<usize, ConvertError> {
+        let mut body = [0; MAX_TELEMETRY_LEN];
+        let len = self.encode_into(&mut body)?;
+        Envelope::new(self.kind().into(), &body[..len]).encode_into(buf)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn encode_with(&self, codec: Codec) -> Vec<u8> {
+        match codec {
+            Codec::Binary => self.encode(),
+            #[cfg(feature = "serde")]
+            Codec::Json => serde_json::to_vec(self).expect("telemetry always serializes"),
+        }
+    }
+
+    pub fn decode_with(
+        codec: Codec,
+        kind: TelemetryKind,
+        data: &[u8],
+    ) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => Self::decode(kind, data),
+            #[cfg(feature = "serde")]
+            Codec::Json => {
+                let telemetry: Telemetry = serde_json::from_slice(data)?;
+                if telemetry.kind() != kind {
+                    return Err(ConvertError::UnexpectedMessageType(telemetry.kind().into()));
+                }
+                Ok(telemetry)
+            }
+        }
+    }
+
+    /// Decodes a payload received on `kind.topic(uuid)`
+    ///
+    /// Accepts both bare and enveloped payloads.
+    pub fn decode(kind: TelemetryKind, data: &[u8]) -> Result<Self, ConvertError> {
+        let data = Envelope::decode_or_legacy(data, kind.into(), kind.payload_len())?.body;
+        if data.len() != kind.payload_len() {
+            return Err(ConvertError::Length(data.len()));
+        }
+
+        let word = |i: usize| [data[i], data[i + 1], data[i + 2], data[i + 3]];
+        let vector = || {
+            Vector3::new(
+                f32::from_be_bytes(word(0)),
+                f32::from_be_bytes(word(4)),
+                f32::from_be_bytes(word(8)),
+            )
+        };
+
+        Ok(match kind {
+            TelemetryKind::ChipTemperature => {
+                Telemetry::ChipTemperature(f32::from_be_bytes(word(0)))
+            }
+            TelemetryKind::AmbientTemperature => {
+                Telemetry::AmbientTemperature(f32::from_be_bytes(word(0)))
+            }
+            TelemetryKind::Humidity => Telemetry::Humidity(f32::from_be_bytes(word(0))),
+            TelemetryKind::Accel => Telemetry::Accel(vector()),
+            TelemetryKind::Gyro => Telemetry::Gyro(vector()),
+            TelemetryKind::StepCount => Telemetry::StepCount(u32::from_be_bytes(word(0))),
+        })
+    }
 }
 
+#[cfg(feature = "std")]
 #[derive(Debug)]
 pub struct RawCommandData<'a> {
     pub path: &'a str,
     pub data: Cow<'a, [u8]>,
 }
 
+#[cfg(feature = "std")]
 impl<'a> TryFrom<Command> for RawCommandData<'a> {
     type Error = ();
 
     fn try_from(value: Command) -> Result<Self, Self::Error> {
-        match value {
-            Command::BoardLed(rgb) => Ok(RawCommandData {
-                data: Cow::Owned(vec![rgb.r, rgb.g, rgb.b]),
-                path: Command::BOARD_LED,
-            }),
-        }
+        Ok(RawCommandData {
+            data: Cow::Owned(value.data().to_vec()),
+            path: value.kind().name(),
+        })
     }
 }
 
+#[derive(Debug)]
+// variants depend on features, which cargo unifies across dependents
+#[non_exhaustive]
 pub enum ConvertError {
     Length(usize),
     InvalidPath,
+    /// payload does not start with `envelope::MAGIC`
+    InvalidHeader,
+    /// envelope version this crate does not know about
+    UnsupportedVersion(u8),
+    UnknownMessageType(u8),
+    UnexpectedMessageType(MessageType),
+    /// the output buffer has to hold at least this many bytes
+    BufferTooSmall(usize),
+    /// a field holds a value that is out of range
+    InvalidData,
+    /// the HMAC does not match topic, nonce and payload
+    #[cfg(feature = "signing")]
+    InvalidSignature,
+    /// the nonce is not larger than the last accepted one
+    #[cfg(feature = "signing")]
+    StaleNonce(u64),
+    /// wrong key or topic, or the payload was tampered with
+    #[cfg(feature = "sealing")]
+    InvalidSeal,
+    #[cfg(feature = "serde")]
+    Json(serde_json::Error),
+}
+
+#[cfg(feature = "serde")]
+impl From<serde_json::Error> for ConvertError {
+    fn from(error: serde_json::Error) -> Self {
+        ConvertError::Json(error)
+    }
 }
 
+#[cfg(feature = "std")]
 impl<'a> TryFrom<RawCommandData<'a>> for Command {
     type Error = ConvertError;
 
     fn try_from(value: RawCommandData) -> Result<Self, Self::Error> {
         //if value.path == Command::BOARD_LED {
-        if value.path == "" {
-            let data: &[u8] = value.data.borrow();
-            let data: [u8; 3] = data
-                .try_into()
-                .map_err(|_| ConvertError::Length(data.len()))?;
-            let rgb = RGB8::new(data[0], data[1], data[2]);
-            Ok(Command::BoardLed(rgb))
+        if value.path.is_empty() {
+            Command::decode(value.data.borrow())
         } else {
             Err(ConvertError::InvalidPath)
         }
@@ -111,10 +767,12 @@ impl<'a> TryFrom<RawCommandData<'a>> for Command {
 /// Handles `.data()` from EspMqttMessage
 ///
 // The message is a slice containing 3 values, and is cast into a ColorData(rgb)
-impl<'a> TryFrom<&[u8]> for ColorData {
+// It may also arrive wrapped in an `Envelope`, the bare 3 bytes are legacy v0.
+impl TryFrom<&[u8]> for ColorData {
     type Error = ConvertError;
 
     fn try_from(message: &[u8]) -> Result<Self, Self::Error> {
+        let message = Envelope::decode_or_legacy(message, MessageType::Color, 3)?.body;
         if message.len() == 3 {
             let rgb = RGB8::new(message[0], message[1], message[2]);
             Ok(ColorData::BoardLed(rgb))
@@ -0,0 +1,258 @@
+//! Firmware updates over the air
+//!
+//! A host publishes `OtaStart` on `ota_topic(uuid)`. The board downloads the
+//! image from `url`, checks it against `sha256`, writes it to the inactive
+//! app partition and reports `OtaReport`s on `ota_status_topic(uuid)` along
+//! the way. After `OtaReport::Done` it restarts into the new firmware.
+//!
+//! Binary: enveloped `MessageType::OtaStart`, the SHA-256 digest (32 bytes),
+//! the length of the version (u8), the version and the URL as UTF-8 in the
+//! remaining bytes.
+//!
+//! Enveloped `MessageType::OtaReport`, a `0` byte followed by the bytes
+//! received and the image size (u32 each, big endian, size `0` if unknown) for
+//! `Progress`, a `1` byte and the version as UTF-8 for `Done`, or a `2` byte and
+//! the reason as UTF-8 for `Failed`.
+
+use core::fmt::{self, Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::ConvertError;
+#[cfg(feature = "std")]
+use crate::{
+    envelope::{Envelope, MessageType},
+    Codec,
+};
+
+/// Length of a SHA-256 digest in bytes
+pub const DIGEST_LEN: usize = 32;
+
+pub fn write_ota_topic(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/ota", uuid)
+}
+
+#[cfg(feature = "std")]
+pub fn ota_topic(uuid: &str) -> String {
+    crate::to_topic(|topic| write_ota_topic(topic, uuid))
+}
+
+pub fn write_ota_status_topic(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/ota/status", uuid)
+}
+
+#[cfg(feature = "std")]
+pub fn ota_status_topic(uuid: &str) -> String {
+    crate::to_topic(|topic| write_ota_status_topic(topic, uuid))
+}
+
+/// Parses a digest written as 64 hex digits, as printed by `sha256sum`
+pub fn parse_digest(hex: &str) -> Result<[u8; DIGEST_LEN], ConvertError> {
+    let hex = hex.as_bytes();
+    if hex.len() != 2 * DIGEST_LEN {
+        return Err(ConvertError::Length(hex.len()));
+    }
+    let nibble = |digit: u8| match digit {
+        b'0'..=b'9' => Ok(digit - b'0'),
+        b'a'..=b'f' => Ok(digit - b'a' + 10),
+        b'A'..=b'F' => Ok(digit - b'A' + 10),
+        _ => Err(ConvertError::InvalidData),
+    };
+    let mut digest = [0; DIGEST_LEN];
+    for (byte, pair) in digest.iter_mut().zip(hex.chunks(2)) {
+        *byte = nibble(pair[0])? << 4 | nibble(pair[1])?;
+    }
+    Ok(digest)
+}
+
+/// Writes `digest` as 64 lower case hex digits
+pub fn write_digest(out: &mut impl Write, digest: &[u8; DIGEST_LEN]) -> fmt::Result {
+    digest
+        .iter()
+        .try_for_each(|byte| write!(out, "{:02x}", byte))
+}
+
+/// Asks a board to update its firmware
+///
+/// As JSON the digest is a hex string.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OtaStart {
+    /// where to download the image, `https://` only
+    pub url: String,
+    #[cfg_attr(feature = "serde", serde(with = "hex_digest"))]
+    pub sha256: [u8; DIGEST_LEN],
+    /// version the image reports, e.g. in its `Heartbeat`
+    pub version: String,
+}
+
+#[cfg(feature = "std")]
+impl OtaStart {
+    pub fn topic(&self, uuid: &str) -> String {
+        ota_topic(uuid)
+    }
+
+    pub fn encode_with(&self, codec: Codec) -> Result<Vec<u8>, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                // versions longer than 255 bytes are cut off at a char boundary
+                let mut version_len = self.version.len().min(u8::MAX as usize);
+                while !self.version.is_char_boundary(version_len) {
+                    version_len -= 1;
+                }
+                let version = &self.version.as_bytes()[..version_len];
+                let mut body = Vec::with_capacity(DIGEST_LEN + 1 + version.len() + self.url.len());
+                body.extend_from_slice(&self.sha256);
+                body.push(version.len() as u8);
+                body.extend_from_slice(version);
+                body.extend_from_slice(self.url.as_bytes());
+                Envelope::new(MessageType::OtaStart, &body).encode()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::to_vec(self)?),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let envelope = Envelope::decode(data)?;
+                if envelope.message_type != MessageType::OtaStart {
+                    return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+                }
+                let body = envelope.body;
+                if body.len() <= DIGEST_LEN {
+                    return Err(ConvertError::Length(body.len()));
+                }
+                let (digest, rest) = body.split_at(DIGEST_LEN);
+                let version_len = rest[0] as usize;
+                let rest = &rest[1..];
+                if rest.len() < version_len {
+                    return Err(ConvertError::Length(body.len()));
+                }
+                let (version, url) = rest.split_at(version_len);
+                let mut sha256 = [0; DIGEST_LEN];
+                sha256.copy_from_slice(digest);
+                Ok(OtaStart {
+                    url: utf8(url)?,
+                    sha256,
+                    version: utf8(version)?,
+                })
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
+
+/// How an update is going
+///
+/// As JSON: `{"state":"progress","received":4096,"total":917504}`,
+/// `{"state":"done","version":"0.2.0"}` or `{"state":"failed","reason":"..."}`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "state", rename_all = "snake_case")
+)]
+pub enum OtaReport {
+    /// `received` bytes of the image are written, `total` is `0` if the
+    /// server did not say
+    Progress { received: u32, total: u32 },
+    /// the image is verified and boots after the restart
+    Done { version: String },
+    /// the update stopped, the board keeps running its current firmware
+    Failed { reason: String },
+}
+
+#[cfg(feature = "std")]
+impl OtaReport {
+    const PROGRESS: u8 = 0;
+    const DONE: u8 = 1;
+    const FAILED: u8 = 2;
+
+    pub fn topic(&self, uuid: &str) -> String {
+        ota_status_topic(uuid)
+    }
+
+    pub fn encode_with(&self, codec: Codec) -> Result<Vec<u8>, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let mut body = Vec::new();
+                match self {
+                    OtaReport::Progress { received, total } => {
+                        body.push(Self::PROGRESS);
+                        body.extend_from_slice(&received.to_be_bytes());
+                        body.extend_from_slice(&total.to_be_bytes());
+                    }
+                    OtaReport::Done { version } => {
+                        body.push(Self::DONE);
+                        body.extend_from_slice(version.as_bytes());
+                    }
+                    OtaReport::Failed { reason } => {
+                        body.push(Self::FAILED);
+                        body.extend_from_slice(reason.as_bytes());
+                    }
+                }
+                Envelope::new(MessageType::OtaReport, &body).encode()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::to_vec(self)?),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let envelope = Envelope::decode(data)?;
+                if envelope.message_type != MessageType::OtaReport {
+                    return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+                }
+                match envelope.body {
+                    [Self::PROGRESS, r0, r1, r2, r3, t0, t1, t2, t3] => Ok(OtaReport::Progress {
+                        received: u32::from_be_bytes([*r0, *r1, *r2, *r3]),
+                        total: u32::from_be_bytes([*t0, *t1, *t2, *t3]),
+                    }),
+                    [Self::PROGRESS, ..] => Err(ConvertError::Length(envelope.body.len())),
+                    [Self::DONE, version @ ..] => Ok(OtaReport::Done {
+                        version: utf8(version)?,
+                    }),
+                    [Self::FAILED, reason @ ..] => Ok(OtaReport::Failed {
+                        reason: utf8(reason)?,
+                    }),
+                    _ => Err(ConvertError::InvalidData),
+                }
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+fn utf8(data: &[u8]) -> Result<String, ConvertError> {
+    Ok(std::str::from_utf8(data)
+        .map_err(|_| ConvertError::InvalidData)?
+        .to_owned())
+}
+
+#[cfg(feature = "serde")]
+mod hex_digest {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::DIGEST_LEN;
+
+    pub fn serialize<S: Serializer>(digest: &[u8; DIGEST_LEN], s: S) -> Result<S::Ok, S::Error> {
+        let mut hex = String::with_capacity(2 * DIGEST_LEN);
+        super::write_digest(&mut hex, digest).expect("writing to a String never fails");
+        s.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<[u8; DIGEST_LEN], D::Error> {
+        let hex = String::deserialize(d)?;
+        super::parse_digest(&hex).map_err(|_| D::Error::custom("expected 64 hex digits"))
+    }
+}
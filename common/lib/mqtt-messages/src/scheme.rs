@@ -0,0 +1,137 @@
+//! Where this crate's topics live in the broker's topic tree
+//!
+//! By default every board's topics start with its UUID, e.g.
+//! `<uuid>/sensor_data/temperature`, and broadcasts go to `all/command/...`.
+//! On a shared broker that collides with other tenants, so a `TopicScheme`
+//! can put everything below a prefix instead:
+//!
+//! ```
+//! use mqtt_messages::{write_hello_topic, TopicScheme, TopicString};
+//!
+//! let uuid = "6188eec9-6d3a-4eac-996f-ac4ab13f312d";
+//! let scheme = TopicScheme::new("org/site/esp-rs").unwrap();
+//! let mut root = TopicString::new();
+//! scheme.write_root(&mut root, uuid).unwrap();
+//! let mut topic = TopicString::new();
+//! write_hello_topic(&mut topic, &root).unwrap();
+//! assert_eq!(
+//!     topic,
+//!     "org/site/esp-rs/6188eec9-6d3a-4eac-996f-ac4ab13f312d/hello"
+//! );
+//! assert_eq!(scheme.uuid(&topic), Some(uuid));
+//! ```
+//!
+//! The per-board topic builders (`hello_topic`, `Telemetry::topic`, ...)
+//! write everything below a board's root, so they take `scheme.root(uuid)`
+//! where they used to take the UUID. With `TopicScheme::BARE` the root is
+//! the UUID itself. Topics that are not below a single board, like fleet
+//! filters and broadcast commands, are built by the scheme directly.
+
+use core::fmt::{self, Write};
+
+use crate::target::Target;
+use crate::{CommandKind, ConvertError};
+
+const SEPARATOR: char = '/';
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TopicScheme<'a> {
+    prefix: Option<&'a str>,
+}
+
+impl<'a> TopicScheme<'a> {
+    /// Every board's topics at the root of the topic tree
+    pub const BARE: TopicScheme<'static> = TopicScheme { prefix: None };
+
+    /// A scheme with all topics below `prefix`, or `BARE` for `""`
+    ///
+    /// The prefix is one or more topic levels without wildcards, leading or
+    /// trailing `/` or empty levels, and must not start with `$`.
+    pub fn new(prefix: &'a str) -> Result<Self, ConvertError> {
+        if prefix.is_empty() {
+            return Ok(Self { prefix: None });
+        }
+        let valid = !prefix.starts_with('$')
+            && !prefix.contains(['+', '#', '\0'])
+            && prefix.split(SEPARATOR).all(|level| !level.is_empty());
+        if !valid {
+            return Err(ConvertError::InvalidPath);
+        }
+        Ok(Self {
+            prefix: Some(prefix),
+        })
+    }
+
+    pub fn prefix(&self) -> Option<&'a str> {
+        self.prefix
+    }
+
+    /// Writes the prefix, if any, followed by `rest`
+    fn write_prefixed(&self, topic: &mut impl Write, rest: &str) -> fmt::Result {
+        if let Some(prefix) = self.prefix {
+            write!(topic, "{}{}", prefix, SEPARATOR)?;
+        }
+        topic.write_str(rest)
+    }
+
+    /// The level all of a board's topics start with
+    pub fn write_root(&self, topic: &mut impl Write, uuid: &str) -> fmt::Result {
+        self.write_prefixed(topic, uuid)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn root(&self, uuid: &str) -> String {
+        crate::to_topic(|topic| self.write_root(topic, uuid))
+    }
+
+    /// `filter` below the prefix, e.g. for `topic_filter::FLEET_HELLO`
+    pub fn write_filter(&self, topic: &mut impl Write, filter: &str) -> fmt::Result {
+        self.write_prefixed(topic, filter)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn filter(&self, filter: &str) -> String {
+        crate::to_topic(|topic| self.write_filter(topic, filter))
+    }
+
+    /// `topic` without the prefix, or `None` if it is not below the prefix
+    pub fn strip<'t>(&self, topic: &'t str) -> Option<&'t str> {
+        match self.prefix {
+            Some(prefix) => topic.strip_prefix(prefix)?.strip_prefix(SEPARATOR),
+            None => Some(topic),
+        }
+    }
+
+    /// The UUID of the board that published `topic`
+    pub fn uuid<'t>(&self, topic: &'t str) -> Option<&'t str> {
+        self.strip(topic)?.split(SEPARATOR).next()
+    }
+
+    /// Everything up to the command name, e.g. `all/command/`
+    pub fn write_cmd_topic_fragment(&self, topic: &mut impl Write, target: Target) -> fmt::Result {
+        self.write_prefixed(topic, "")?;
+        target.write_cmd_topic_fragment(topic)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn cmd_topic_fragment(&self, target: Target) -> String {
+        crate::to_topic(|topic| self.write_cmd_topic_fragment(topic, target))
+    }
+
+    /// Subscription filter for all commands sent to `target`
+    pub fn write_cmd_filter(&self, topic: &mut impl Write, target: Target) -> fmt::Result {
+        self.write_cmd_topic_fragment(topic, target)?;
+        topic.write_str(crate::topic_filter::SINGLE_LEVEL)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn cmd_filter(&self, target: Target) -> String {
+        crate::to_topic(|topic| self.write_cmd_filter(topic, target))
+    }
+
+    /// Splits a command topic in any of the three forms into its target and
+    /// the kind of command
+    pub fn parse_cmd_topic<'t>(&self, topic: &'t str) -> Option<(Target<'t>, CommandKind)> {
+        Target::parse_cmd_topic(self.strip(topic)?)
+    }
+}
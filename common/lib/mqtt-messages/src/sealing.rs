@@ -0,0 +1,182 @@
+//! ChaCha20-Poly1305 sealed payloads
+//!
+//! Sealing wraps any encoded message so that brokers and other subscribers
+//! see neither readings nor commands. Every board has its own key, derived
+//! from a shared secret and its UUID with `device_key`.
+//!
+//! Binary: enveloped `MessageType::Sealed`. The body is the 12 byte nonce,
+//! the ciphertext and the 16 byte Poly1305 tag. The topic is authenticated as
+//! associated data, so a sealed payload cannot be moved to another topic.
+//!
+//! Board and host use the same key, so their nonces must never collide, and a
+//! nonce must never repeat after a reboot. A nonce is therefore made of
+//! - the sending `Party` (1 byte)
+//! - a session number (u32, big endian) that is different every time a
+//!   `Sealer` is created: the board uses a boot counter kept in flash, the
+//!   host a random number
+//! - a message counter within the session (7 bytes, big endian)
+//!
+//! An `Opener` only accepts payloads sealed by the other party, so nobody can
+//! reflect a party's own messages back at it.
+
+use chacha20poly1305::{aead::AeadInPlace, ChaCha20Poly1305, KeyInit, Nonce, Tag};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::envelope::{Envelope, MessageType, HEADER_LEN};
+use crate::ConvertError;
+
+pub const KEY_LEN: usize = 32;
+pub const NONCE_LEN: usize = 12;
+pub const TAG_LEN: usize = 16;
+
+/// Bytes a sealed payload is longer than the original
+pub const SEALED_OVERHEAD: usize = HEADER_LEN + NONCE_LEN + TAG_LEN;
+
+/// Messages a single session can seal before its counter runs out
+const MAX_COUNTER: u64 = 1 << 56;
+
+/// Separates sealing keys from other uses of the shared secret
+const KEY_CONTEXT: &[u8] = b"mqtt-messages sealing key v1";
+
+pub type Key = [u8; KEY_LEN];
+
+/// The sealing key of the board with `uuid`
+///
+/// This is the HMAC-SHA256 of the UUID keyed with `secret`, so a leaked board
+/// key does not reveal the keys of the other boards.
+pub fn device_key(secret: &[u8], uuid: &str) -> Key {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(secret).expect("HMAC takes keys of any length");
+    mac.update(KEY_CONTEXT);
+    mac.update(uuid.as_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+/// Who sealed a payload, the first byte of every nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Party {
+    Board = 0,
+    Host = 1,
+}
+
+/// Seals payloads with unique nonces
+pub struct Sealer {
+    cipher: ChaCha20Poly1305,
+    party: Party,
+    session: u32,
+    counter: u64,
+}
+
+impl Sealer {
+    /// `session` must not have been used by `party` with this key before
+    pub fn new(key: &Key, party: Party, session: u32) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(key.into()),
+            party,
+            session,
+            counter: 0,
+        }
+    }
+
+    /// Writes the sealed `payload` to `buf`, returning the number of bytes used
+    pub fn seal_into(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        buf: &mut [u8],
+    ) -> Result<usize, ConvertError> {
+        let body_len = NONCE_LEN + payload.len() + TAG_LEN;
+        let header = Envelope::header(MessageType::Sealed, body_len)?;
+        let body = buf
+            .get_mut(HEADER_LEN..HEADER_LEN + body_len)
+            .ok_or(ConvertError::BufferTooSmall(HEADER_LEN + body_len))?;
+        assert!(self.counter < MAX_COUNTER, "sealing session exhausted");
+
+        let nonce = self.next_nonce();
+        let (nonce_buf, rest) = body.split_at_mut(NONCE_LEN);
+        let (ciphertext, tag) = rest.split_at_mut(payload.len());
+        nonce_buf.copy_from_slice(&nonce);
+        ciphertext.copy_from_slice(payload);
+        let computed = self
+            .cipher
+            .encrypt_in_place_detached(Nonce::from_slice(&nonce), topic.as_bytes(), ciphertext)
+            .map_err(|_| ConvertError::InvalidSeal)?;
+        tag.copy_from_slice(&computed);
+
+        buf[..HEADER_LEN].copy_from_slice(&header);
+        Ok(HEADER_LEN + body_len)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn seal(&mut self, topic: &str, payload: &[u8]) -> Result<Vec<u8>, ConvertError> {
+        let mut data = vec![0; SEALED_OVERHEAD + payload.len()];
+        self.seal_into(topic, payload, &mut data)?;
+        Ok(data)
+    }
+
+    fn next_nonce(&mut self) -> [u8; NONCE_LEN] {
+        let mut nonce = [0; NONCE_LEN];
+        nonce[0] = self.party as u8;
+        nonce[1..5].copy_from_slice(&self.session.to_be_bytes());
+        nonce[5..].copy_from_slice(&self.counter.to_be_bytes()[1..]);
+        self.counter += 1;
+        nonce
+    }
+}
+
+/// Opens payloads that `sender` sealed with the same key
+pub struct Opener {
+    cipher: ChaCha20Poly1305,
+    sender: Party,
+}
+
+impl Opener {
+    pub fn new(key: &Key, sender: Party) -> Self {
+        Self {
+            cipher: ChaCha20Poly1305::new(key.into()),
+            sender,
+        }
+    }
+
+    /// Decrypts `data` in place, returning the original payload
+    pub fn open_in_place<'a>(
+        &self,
+        topic: &str,
+        data: &'a mut [u8],
+    ) -> Result<&'a mut [u8], ConvertError> {
+        let body_len = {
+            let envelope = Envelope::decode(data)?;
+            if envelope.message_type != MessageType::Sealed {
+                return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+            }
+            envelope.body.len()
+        };
+        if body_len < NONCE_LEN + TAG_LEN {
+            return Err(ConvertError::Length(body_len));
+        }
+
+        let body = &mut data[HEADER_LEN..];
+        let (nonce, rest) = body.split_at_mut(NONCE_LEN);
+        if nonce[0] != self.sender as u8 {
+            return Err(ConvertError::InvalidSeal);
+        }
+        let (ciphertext, tag) = rest.split_at_mut(body_len - NONCE_LEN - TAG_LEN);
+        self.cipher
+            .decrypt_in_place_detached(
+                Nonce::from_slice(nonce),
+                topic.as_bytes(),
+                ciphertext,
+                Tag::from_slice(tag),
+            )
+            .map_err(|_| ConvertError::InvalidSeal)?;
+        Ok(ciphertext)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn open(&self, topic: &str, data: &[u8]) -> Result<Vec<u8>, ConvertError> {
+        let mut data = data.to_vec();
+        Ok(self.open_in_place(topic, &mut data)?.to_vec())
+    }
+}
@@ -0,0 +1,147 @@
+//! HMAC-SHA256 signed commands
+//!
+//! On a shared broker anyone who knows a board's UUID can publish to its
+//! command topics. With a per-device secret key, the host wraps each command
+//! payload with `Signer` and the board only acts on payloads that pass its
+//! `Verifier`.
+//!
+//! Binary: enveloped `MessageType::Signed`. The body is the nonce (u64, big
+//! endian), the 32 byte tag and the original payload. The tag is the
+//! HMAC-SHA256 of the topic length (u16, big endian), the topic, the nonce and
+//! the payload, so a signed payload cannot be replayed on another topic.
+//!
+//! Nonces must increase with every message. The `Verifier` rejects any nonce
+//! that is not larger than the last one it accepted. To keep doing so across
+//! restarts, store `Verifier::last_nonce` before acting on a payload and start
+//! with `Verifier::with_last_nonce`.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::envelope::{Envelope, MessageType, HEADER_LEN};
+use crate::ConvertError;
+
+pub const NONCE_LEN: usize = 8;
+pub const TAG_LEN: usize = 32;
+
+/// Bytes a signed payload is longer than the original
+pub const SIGNED_OVERHEAD: usize = HEADER_LEN + NONCE_LEN + TAG_LEN;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// A payload that passed verification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signed<'a> {
+    pub nonce: u64,
+    pub payload: &'a [u8],
+}
+
+/// Signs payloads with increasing nonces
+#[derive(Debug, Clone)]
+pub struct Signer<'k> {
+    key: &'k [u8],
+    next_nonce: u64,
+}
+
+impl<'k> Signer<'k> {
+    /// `first_nonce` has to be larger than any nonce used with `key` before,
+    /// e.g. the current Unix time in milliseconds
+    pub fn new(key: &'k [u8], first_nonce: u64) -> Self {
+        Self {
+            key,
+            next_nonce: first_nonce,
+        }
+    }
+
+    /// Writes the signed `payload` to `buf`, returning the number of bytes used
+    pub fn sign_into(
+        &mut self,
+        topic: &str,
+        payload: &[u8],
+        buf: &mut [u8],
+    ) -> Result<usize, ConvertError> {
+        let body_len = NONCE_LEN + TAG_LEN + payload.len();
+        let header = Envelope::header(MessageType::Signed, body_len)?;
+        let body = buf
+            .get_mut(HEADER_LEN..HEADER_LEN + body_len)
+            .ok_or(ConvertError::BufferTooSmall(HEADER_LEN + body_len))?;
+
+        let nonce = self.next_nonce;
+        let tag = tag(self.key, topic, nonce, payload).finalize().into_bytes();
+        body[..NONCE_LEN].copy_from_slice(&nonce.to_be_bytes());
+        body[NONCE_LEN..NONCE_LEN + TAG_LEN].copy_from_slice(&tag);
+        body[NONCE_LEN + TAG_LEN..].copy_from_slice(payload);
+        self.next_nonce += 1;
+
+        buf[..HEADER_LEN].copy_from_slice(&header);
+        Ok(HEADER_LEN + body_len)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn sign(&mut self, topic: &str, payload: &[u8]) -> Result<Vec<u8>, ConvertError> {
+        let mut data = vec![0; SIGNED_OVERHEAD + payload.len()];
+        self.sign_into(topic, payload, &mut data)?;
+        Ok(data)
+    }
+}
+
+/// Checks signatures and rejects replayed nonces
+#[derive(Debug, Clone)]
+pub struct Verifier<'k> {
+    key: &'k [u8],
+    last_nonce: Option<u64>,
+}
+
+impl<'k> Verifier<'k> {
+    pub fn new(key: &'k [u8]) -> Self {
+        Self::with_last_nonce(key, None)
+    }
+
+    /// A verifier that only accepts nonces larger than `last_nonce`, e.g.
+    /// the last one accepted before a reboot
+    pub fn with_last_nonce(key: &'k [u8], last_nonce: Option<u64>) -> Self {
+        Self { key, last_nonce }
+    }
+
+    /// The nonce of the last accepted payload
+    pub fn last_nonce(&self) -> Option<u64> {
+        self.last_nonce
+    }
+
+    /// Returns the original payload if `data` was signed for `topic` with
+    /// this key and a nonce that has not been seen yet
+    pub fn verify<'a>(&mut self, topic: &str, data: &'a [u8]) -> Result<Signed<'a>, ConvertError> {
+        let envelope = Envelope::decode(data)?;
+        if envelope.message_type != MessageType::Signed {
+            return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+        }
+        let body = envelope.body;
+        if body.len() < NONCE_LEN + TAG_LEN {
+            return Err(ConvertError::Length(body.len()));
+        }
+        let (nonce, rest) = body.split_at(NONCE_LEN);
+        let (expected, payload) = rest.split_at(TAG_LEN);
+        let nonce = u64::from_be_bytes(nonce.try_into().expect("split at NONCE_LEN"));
+
+        tag(self.key, topic, nonce, payload)
+            .verify_slice(expected)
+            .map_err(|_| ConvertError::InvalidSignature)?;
+        if let Some(last_nonce) = self.last_nonce {
+            if nonce <= last_nonce {
+                return Err(ConvertError::StaleNonce(nonce));
+            }
+        }
+        self.last_nonce = Some(nonce);
+
+        Ok(Signed { nonce, payload })
+    }
+}
+
+fn tag(key: &[u8], topic: &str, nonce: u64, payload: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(&(topic.len() as u16).to_be_bytes());
+    mac.update(topic.as_bytes());
+    mac.update(&nonce.to_be_bytes());
+    mac.update(payload);
+    mac
+}
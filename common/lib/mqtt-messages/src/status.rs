@@ -0,0 +1,174 @@
+//! Board presence and health
+//!
+//! A board publishes `Status::Online` (retained) on `status_topic(uuid)`
+//! periodically and registers `Status::Offline` as its MQTT Last Will, so the
+//! broker announces a board that disappears without saying goodbye.
+//!
+//! Binary: enveloped `MessageType::Status`. `Offline` is a single `0` byte,
+//! `Online` is a `1` byte followed by the `Heartbeat`: uptime (u32, seconds),
+//! free heap (u32, bytes), RSSI (i8, dBm, `i8::MIN` if unknown), reset reason
+//! (u8) and the firmware version as UTF-8 in the remaining bytes.
+
+use core::fmt::{self, Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "std")]
+use crate::envelope::{Envelope, MessageType};
+#[cfg(feature = "std")]
+use crate::{Codec, ConvertError};
+
+pub fn write_status_topic(topic: &mut impl Write, uuid: &str) -> fmt::Result {
+    write!(topic, "{}/status", uuid)
+}
+
+#[cfg(feature = "std")]
+pub fn status_topic(uuid: &str) -> String {
+    crate::to_topic(|topic| write_status_topic(topic, uuid))
+}
+
+/// Why the chip last (re)started, as reported by `esp_reset_reason()`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum ResetReason {
+    Unknown = 0,
+    PowerOn = 1,
+    /// reset pin
+    External = 2,
+    /// `esp_restart()`
+    Software = 3,
+    Panic = 4,
+    InterruptWatchdog = 5,
+    TaskWatchdog = 6,
+    Watchdog = 7,
+    DeepSleep = 8,
+    Brownout = 9,
+    Sdio = 10,
+}
+
+/// Maps `esp_reset_reason_t` values, anything unknown becomes `Unknown`
+impl From<u32> for ResetReason {
+    fn from(value: u32) -> Self {
+        match value {
+            1 => ResetReason::PowerOn,
+            2 => ResetReason::External,
+            3 => ResetReason::Software,
+            4 => ResetReason::Panic,
+            5 => ResetReason::InterruptWatchdog,
+            6 => ResetReason::TaskWatchdog,
+            7 => ResetReason::Watchdog,
+            8 => ResetReason::DeepSleep,
+            9 => ResetReason::Brownout,
+            10 => ResetReason::Sdio,
+            _ => ResetReason::Unknown,
+        }
+    }
+}
+
+/// Health of a running board
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Heartbeat {
+    pub uptime_s: u32,
+    pub firmware_version: String,
+    /// signal strength of the access point, dBm
+    pub rssi: Option<i8>,
+    pub free_heap: u32,
+    pub reset_reason: ResetReason,
+}
+
+/// As JSON: `{"state":"online","uptime_s":42,...}` or `{"state":"offline"}`
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(tag = "state", rename_all = "snake_case")
+)]
+pub enum Status {
+    Online(Heartbeat),
+    Offline,
+}
+
+#[cfg(feature = "std")]
+impl Status {
+    const OFFLINE: u8 = 0;
+    const ONLINE: u8 = 1;
+
+    /// `rssi` of a binary heartbeat that was sent without a Wi-Fi connection
+    const NO_RSSI: i8 = i8::MIN;
+
+    const HEARTBEAT_LEN: usize = 4 + 4 + 1 + 1;
+
+    pub fn topic(&self, uuid: &str) -> String {
+        status_topic(uuid)
+    }
+
+    pub fn encode_with(&self, codec: Codec) -> Result<Vec<u8>, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let mut body = Vec::with_capacity(1 + Self::HEARTBEAT_LEN);
+                match self {
+                    Status::Offline => body.push(Self::OFFLINE),
+                    Status::Online(heartbeat) => {
+                        body.push(Self::ONLINE);
+                        body.extend_from_slice(&heartbeat.uptime_s.to_be_bytes());
+                        body.extend_from_slice(&heartbeat.free_heap.to_be_bytes());
+                        body.extend_from_slice(
+                            &heartbeat.rssi.unwrap_or(Self::NO_RSSI).to_be_bytes(),
+                        );
+                        body.push(heartbeat.reset_reason as u8);
+                        body.extend_from_slice(heartbeat.firmware_version.as_bytes());
+                    }
+                }
+                Envelope::new(MessageType::Status, &body).encode()
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::to_vec(self)?),
+        }
+    }
+
+    pub fn decode_with(codec: Codec, data: &[u8]) -> Result<Self, ConvertError> {
+        match codec {
+            Codec::Binary => {
+                let envelope = Envelope::decode(data)?;
+                if envelope.message_type != MessageType::Status {
+                    return Err(ConvertError::UnexpectedMessageType(envelope.message_type));
+                }
+                match envelope.body {
+                    [Self::OFFLINE] => Ok(Status::Offline),
+                    [Self::ONLINE, heartbeat @ ..] if heartbeat.len() >= Self::HEARTBEAT_LEN => {
+                        let (fixed, version) = heartbeat.split_at(Self::HEARTBEAT_LEN);
+                        let rssi = fixed[8] as i8;
+                        Ok(Status::Online(Heartbeat {
+                            uptime_s: u32::from_be_bytes([fixed[0], fixed[1], fixed[2], fixed[3]]),
+                            free_heap: u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]),
+                            rssi: if rssi == Self::NO_RSSI {
+                                None
+                            } else {
+                                Some(rssi)
+                            },
+                            reset_reason: ResetReason::from(fixed[9] as u32),
+                            firmware_version: std::str::from_utf8(version)
+                                .map_err(|_| ConvertError::InvalidData)?
+                                .to_owned(),
+                        }))
+                    }
+                    [Self::OFFLINE | Self::ONLINE, ..] => {
+                        Err(ConvertError::Length(envelope.body.len()))
+                    }
+                    _ => Err(ConvertError::InvalidData),
+                }
+            }
+            #[cfg(feature = "serde")]
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Who a `Command` is addressed to
+//!
+//! Besides a single board (`<uuid>/command/<name>`), commands can go to every
+//! board (`all/command/<name>`) or to the boards that joined a group
+//! (`group/<group>/command/<name>`). Boards join and leave groups with
+//! `Command::JoinGroup` and `Command::LeaveGroup`.
+//!
+//! These topics sit below the prefix of a `TopicScheme`, which builds and
+//! parses them.
+
+use core::fmt::{self, Write};
+
+use crate::{CommandKind, ConvertError, GroupName};
+
+const ALL: &str = "all";
+const GROUP: &str = "group";
+const COMMAND: &str = "command";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target<'a> {
+    /// the board with this UUID
+    Device(&'a str),
+    /// every board
+    All,
+    /// every board in the named group
+    Group(&'a str),
+}
+
+impl<'a> Target<'a> {
+    /// Everything up to the command name, e.g. `all/command/`, without prefix
+    pub fn write_cmd_topic_fragment(&self, topic: &mut impl Write) -> fmt::Result {
+        match self {
+            Target::Device(uuid) => write!(topic, "{}/{}/", uuid, COMMAND),
+            Target::All => write!(topic, "{}/{}/", ALL, COMMAND),
+            Target::Group(name) => write!(topic, "{}/{}/{}/", GROUP, name, COMMAND),
+        }
+    }
+
+    /// Splits a command topic without prefix into its target and the kind of
+    /// command
+    pub(crate) fn parse_cmd_topic(topic: &'a str) -> Option<(Self, CommandKind)> {
+        let (prefix, name) = topic.rsplit_once('/')?;
+        let prefix = prefix.strip_suffix(COMMAND)?.strip_suffix('/')?;
+        let kind = CommandKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)?;
+
+        let target = match prefix.split_once('/') {
+            None if prefix == ALL => Target::All,
+            // `group/command/...` lacks the group name
+            None if !prefix.is_empty() && prefix != GROUP => Target::Device(prefix),
+            Some((GROUP, name)) if is_group_name(name) => Target::Group(name),
+            _ => return None,
+        };
+        Some((target, kind))
+    }
+}
+
+/// Group names are 1 to 32 ASCII letters, digits, `-` or `_`
+pub fn is_group_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= crate::GROUP_NAME_CAPACITY
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+pub fn group_name(name: &str) -> Result<GroupName, ConvertError> {
+    if !is_group_name(name) {
+        return Err(ConvertError::InvalidData);
+    }
+    let mut group = GroupName::new();
+    group
+        .push_str(name)
+        .map_err(|_| ConvertError::InvalidData)?;
+    Ok(group)
+}
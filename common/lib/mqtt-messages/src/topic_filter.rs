@@ -0,0 +1,172 @@
+//! MQTT topic filters with `+` and `#` wildcards
+//!
+//! Matching follows section 4.7 of the MQTT 3.1.1 spec:
+//! - `+` matches exactly one topic level, which may be empty
+//! - `#` matches the parent level and any number of child levels, and must be
+//!   the last character of the filter
+//! - topics starting with `$` (e.g. `$SYS/...`) are not matched by filters
+//!   that start with a wildcard
+//!
+//! ```
+//! use mqtt_messages::topic_filter::TopicFilter;
+//!
+//! let filter = TopicFilter::new("+/sensor_data/#").unwrap();
+//! let topic = "6188eec9-6d3a-4eac-996f-ac4ab13f312d/sensor_data/temperature";
+//! assert!(filter.matches(topic));
+//! assert_eq!(filter.uuid(topic), Some("6188eec9-6d3a-4eac-996f-ac4ab13f312d"));
+//! ```
+
+use core::fmt;
+
+const SEPARATOR: char = '/';
+pub(crate) const SINGLE_LEVEL: &str = "+";
+const MULTI_LEVEL: &str = "#";
+
+/// Longest topic or filter that fits the MQTT length prefix
+pub const MAX_LEN: usize = 65_535;
+
+/// All sensor readings of every board
+pub const FLEET_SENSOR_DATA: &str = "+/sensor_data/+";
+/// Hello messages of every board
+pub const FLEET_HELLO: &str = "+/hello";
+/// Presence and health of every board
+pub const FLEET_STATUS: &str = "+/status";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TopicFilterError {
+    Empty,
+    TooLong(usize),
+    /// filters and topics must not contain U+0000
+    NullCharacter,
+    /// `+` has to fill a whole level
+    InvalidSingleLevelWildcard,
+    /// `#` has to fill the last level
+    InvalidMultiLevelWildcard,
+}
+
+impl fmt::Display for TopicFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TopicFilterError::Empty => write!(f, "empty topic filter"),
+            TopicFilterError::TooLong(len) => {
+                write!(
+                    f,
+                    "topic filter is {} bytes, at most {} allowed",
+                    len, MAX_LEN
+                )
+            }
+            TopicFilterError::NullCharacter => write!(f, "topic filter contains U+0000"),
+            TopicFilterError::InvalidSingleLevelWildcard => {
+                write!(f, "`+` must occupy an entire topic level")
+            }
+            TopicFilterError::InvalidMultiLevelWildcard => {
+                write!(f, "`#` must be the last topic level")
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for TopicFilterError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopicFilter<'a> {
+    filter: &'a str,
+}
+
+impl<'a> TopicFilter<'a> {
+    pub fn new(filter: &'a str) -> Result<Self, TopicFilterError> {
+        check_length(filter)?;
+
+        let mut levels = filter.split(SEPARATOR).peekable();
+        while let Some(level) = levels.next() {
+            if level.contains('#') && (level != MULTI_LEVEL || levels.peek().is_some()) {
+                return Err(TopicFilterError::InvalidMultiLevelWildcard);
+            }
+            if level.contains('+') && level != SINGLE_LEVEL {
+                return Err(TopicFilterError::InvalidSingleLevelWildcard);
+            }
+        }
+
+        Ok(Self { filter })
+    }
+
+    pub fn as_str(&self) -> &'a str {
+        self.filter
+    }
+
+    /// Whether `topic` would be delivered to a subscription with this filter
+    ///
+    /// Topic names containing wildcards never match.
+    pub fn matches(&self, topic: &str) -> bool {
+        self.match_levels(topic, |_| ())
+    }
+
+    /// The topic level matched by the `n`th `+` of the filter (counting from 0)
+    pub fn wildcard<'t>(&self, topic: &'t str, n: usize) -> Option<&'t str> {
+        let mut index = 0;
+        let mut found = None;
+        let matched = self.match_levels(topic, |level| {
+            if index == n {
+                found = Some(level);
+            }
+            index += 1;
+        });
+        if matched {
+            found
+        } else {
+            None
+        }
+    }
+
+    /// The UUID of the board that published `topic`
+    ///
+    /// All of this crate's topics start with the UUID, so this is the level
+    /// matched by the first `+` of e.g. `+/sensor_data/+`. That still holds
+    /// below the prefix of a `TopicScheme`, which has no wildcards.
+    pub fn uuid<'t>(&self, topic: &'t str) -> Option<&'t str> {
+        self.wildcard(topic, 0)
+    }
+
+    fn match_levels<'t>(&self, topic: &'t str, mut on_single_level: impl FnMut(&'t str)) -> bool {
+        if check_length(topic).is_err() || topic.contains(['+', '#']) {
+            return false;
+        }
+        if topic.starts_with('$')
+            && (self.filter.starts_with(SINGLE_LEVEL) || self.filter.starts_with(MULTI_LEVEL))
+        {
+            return false;
+        }
+
+        let mut filter_levels = self.filter.split(SEPARATOR);
+        let mut topic_levels = topic.split(SEPARATOR);
+        loop {
+            match (filter_levels.next(), topic_levels.next()) {
+                (Some(MULTI_LEVEL), _) => return true,
+                (Some(SINGLE_LEVEL), Some(level)) => on_single_level(level),
+                (Some(expected), Some(level)) if expected == level => {}
+                (None, None) => return true,
+                _ => return false,
+            }
+        }
+    }
+}
+
+impl fmt::Display for TopicFilter<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.filter)
+    }
+}
+
+fn check_length(s: &str) -> Result<(), TopicFilterError> {
+    if s.is_empty() {
+        return Err(TopicFilterError::Empty);
+    }
+    if s.len() > MAX_LEN {
+        return Err(TopicFilterError::TooLong(s.len()));
+    }
+    if s.contains('\0') {
+        return Err(TopicFilterError::NullCharacter);
+    }
+    Ok(())
+}
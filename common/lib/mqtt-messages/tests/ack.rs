@@ -0,0 +1,224 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{assert_too_short, enveloped, UUID};
+use mqtt_messages::ack::{response_filter, response_topic};
+use mqtt_messages::envelope::{MessageType, HEADER_LEN, MAGIC, VERSION};
+use mqtt_messages::{
+    Ack, AckStatus, Codec, Command, CommandKind, CommandRequest, ConfigKey, ConvertError, RGB8,
+};
+
+fn requests() -> [CommandRequest; 3] {
+    [
+        CommandRequest::new(7, Command::BoardLed(RGB8::new(10, 20, 30))),
+        CommandRequest::new(
+            u32::MAX,
+            Command::ConfigGet {
+                key: ConfigKey::PublishInterval,
+            },
+        ),
+        CommandRequest {
+            id: None,
+            command: Command::BoardLed(RGB8::new(1, 2, 3)),
+        },
+    ]
+}
+
+fn acks() -> [Ack; 4] {
+    [
+        Ack::new(7, AckStatus::Ok, ""),
+        Ack::new(8, AckStatus::Rejected, "expected 3 bytes"),
+        Ack::new(9, AckStatus::Failed, "LED driver: ÄÖÜ"),
+        Ack::new(u32::MAX, AckStatus::Unsupported, "no such sensor"),
+    ]
+}
+
+#[test]
+fn request_round_trip() {
+    for request in requests() {
+        let data = request.encode_with(Codec::Binary);
+        let kind = request.command.kind();
+        assert_eq!(
+            CommandRequest::decode_kind_with(Codec::Binary, kind, &data).unwrap(),
+            request
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn request_json_round_trip() {
+    for request in requests() {
+        let data = request.encode_with(Codec::Json);
+        let kind = request.command.kind();
+        assert_eq!(
+            CommandRequest::decode_kind_with(Codec::Json, kind, &data).unwrap(),
+            request
+        );
+    }
+}
+
+#[test]
+fn request_layout() {
+    let data = requests()[0].encode_with(Codec::Binary);
+    let header = [MAGIC | VERSION, MessageType::CommandRequest as u8, 0, 7];
+    assert_eq!(data[..HEADER_LEN], header);
+    assert_eq!(data[HEADER_LEN..], [0, 0, 0, 7, 10, 20, 30]);
+    // without an id, a request is a bare command
+    assert_eq!(requests()[2].encode_with(Codec::Binary), [1, 2, 3]);
+}
+
+#[test]
+fn plain_command_is_a_request_without_id() {
+    let command = Command::BoardLed(RGB8::new(4, 5, 6));
+    let request = CommandRequest::decode_with(Codec::Binary, &command.data()).unwrap();
+    assert_eq!(request.id, None);
+    assert_eq!(request.command, command);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn plain_json_command_is_a_request_without_id() {
+    let command = Command::BoardLed(RGB8::new(4, 5, 6));
+    let data = command.encode_with(Codec::Json);
+    let request = CommandRequest::decode_with(Codec::Json, &data).unwrap();
+    assert_eq!(request.id, None);
+    assert_eq!(request.command, command);
+}
+
+#[test]
+fn request_without_whole_id() {
+    let decode = |data: &[u8]| CommandRequest::decode_with(Codec::Binary, data);
+    assert_too_short(MessageType::CommandRequest, &[0, 0, 0, 7], decode);
+    // the command after the id is checked like a bare one
+    assert_too_short(
+        MessageType::CommandRequest,
+        &[0, 0, 0, 7, 10, 20, 30],
+        decode,
+    );
+}
+
+#[test]
+fn request_invalid_config_key() {
+    let data = enveloped(MessageType::CommandRequest, &[0, 0, 0, 7, 0xFF]);
+    assert!(matches!(
+        CommandRequest::decode_kind_with(Codec::Binary, CommandKind::ConfigGet, &data),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn request_json_wrong_kind() {
+    let data = requests()[1].encode_with(Codec::Json);
+    assert!(matches!(
+        CommandRequest::decode_kind_with(Codec::Json, CommandKind::BoardLed, &data),
+        Err(ConvertError::InvalidPath)
+    ));
+}
+
+#[test]
+fn ack_round_trip() {
+    for ack in acks() {
+        let data = ack.encode_with(Codec::Binary).unwrap();
+        assert_eq!(Ack::decode_with(Codec::Binary, &data).unwrap(), ack);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn ack_json_round_trip() {
+    for ack in acks() {
+        let data = ack.encode_with(Codec::Json).unwrap();
+        assert_eq!(Ack::decode_with(Codec::Json, &data).unwrap(), ack);
+    }
+    assert_eq!(
+        Ack::new(7, AckStatus::Unsupported, "")
+            .encode_with(Codec::Json)
+            .unwrap(),
+        br#"{"id":7,"status":"unsupported","detail":""}"#
+    );
+}
+
+#[test]
+fn ack_layout() {
+    let data = acks()[2].encode_with(Codec::Binary).unwrap();
+    let detail = "LED driver: ÄÖÜ".as_bytes();
+    assert_eq!(
+        data[..HEADER_LEN],
+        [MAGIC | VERSION, MessageType::Ack as u8, 0, 23]
+    );
+    assert_eq!(data[HEADER_LEN..HEADER_LEN + 5], [0, 0, 0, 9, 2]);
+    assert_eq!(data[HEADER_LEN + 5..], *detail);
+}
+
+#[test]
+fn ack_without_status() {
+    assert_too_short(MessageType::Ack, &[0, 0, 0, 7, 0], |data| {
+        Ack::decode_with(Codec::Binary, data)
+    });
+}
+
+#[test]
+fn ack_invalid_status() {
+    for status in [4, 0xFF] {
+        let data = enveloped(MessageType::Ack, &[0, 0, 0, 7, status]);
+        assert!(matches!(
+            Ack::decode_with(Codec::Binary, &data),
+            Err(ConvertError::InvalidData)
+        ));
+    }
+    for code in 0..=u8::MAX {
+        if let Ok(status) = AckStatus::try_from(code) {
+            assert_eq!(status as u8, code);
+        }
+    }
+}
+
+#[test]
+fn ack_invalid_detail() {
+    let data = enveloped(MessageType::Ack, &[0, 0, 0, 7, 0, 0xFF, 0xFE]);
+    assert!(matches!(
+        Ack::decode_with(Codec::Binary, &data),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[test]
+fn ack_is_not_a_request() {
+    let data = requests()[0].encode_with(Codec::Binary);
+    assert!(matches!(
+        Ack::decode_with(Codec::Binary, &data),
+        Err(ConvertError::UnexpectedMessageType(
+            MessageType::CommandRequest
+        ))
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn ack_json_invalid() {
+    for data in [
+        &br#"{"id":7,"status":"maybe","detail":""}"#[..],
+        br#"{"id":-7,"status":"ok","detail":""}"#,
+    ] {
+        assert!(matches!(
+            Ack::decode_with(Codec::Json, data),
+            Err(ConvertError::Json(_))
+        ));
+    }
+}
+
+#[test]
+fn topics() {
+    assert_eq!(
+        response_topic(UUID, 7),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/response/7"
+    );
+    assert_eq!(
+        response_filter(UUID),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/response/+"
+    );
+    assert_eq!(acks()[0].topic(UUID), response_topic(UUID, 7));
+}
@@ -0,0 +1,225 @@
+mod common;
+
+use common::{assert_exact_len, enveloped};
+use mqtt_messages::animation::{MAX_KEYFRAMES, MAX_LEN};
+use mqtt_messages::envelope::MessageType;
+use mqtt_messages::heapless::Vec;
+use mqtt_messages::{Animation, Command, CommandKind, ConvertError, Keyframe, Pattern, RGB8};
+
+const RED: RGB8 = RGB8::new(255, 0, 0);
+const BLUE: RGB8 = RGB8::new(0, 0, 255);
+
+fn animation(pattern: Pattern) -> Animation {
+    Animation {
+        pattern,
+        period_ms: 1_000,
+        repeat: 3,
+        priority: 1,
+    }
+}
+
+fn keyframes(times: &[u32]) -> Pattern {
+    let mut keyframes = Vec::new();
+    for at_ms in times {
+        keyframes.push(Keyframe::new(*at_ms, RED)).unwrap();
+    }
+    Pattern::Keyframes(keyframes)
+}
+
+fn animations() -> [Animation; 6] {
+    [
+        animation(Pattern::Solid(RED)),
+        animation(Pattern::Blink(BLUE)),
+        animation(Pattern::Breathe(RGB8::new(1, 2, 3))),
+        Animation {
+            period_ms: u32::MAX,
+            repeat: 0,
+            priority: u8::MAX,
+            ..animation(Pattern::Rainbow)
+        },
+        animation(keyframes(&[0])),
+        animation(keyframes(&[0, 100, 200, 300, 400, 500, 600, 999])),
+    ]
+}
+
+fn encode(animation: &Animation) -> ([u8; MAX_LEN], usize) {
+    let mut buf = [0; MAX_LEN];
+    let len = animation.encode_into(&mut buf).unwrap();
+    (buf, len)
+}
+
+fn decode(data: &[u8]) -> Result<Command, ConvertError> {
+    Command::decode_kind(CommandKind::Animation, data)
+}
+
+#[test]
+fn round_trip() {
+    for animation in animations() {
+        let (buf, len) = encode(&animation);
+        assert_eq!(Animation::decode(&buf[..len]).unwrap(), animation);
+
+        let command = Command::Animation(animation);
+        assert_eq!(decode(&command.data()).unwrap(), command);
+        let data = enveloped(MessageType::Command, &command.data());
+        assert_eq!(decode(&data).unwrap(), command);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    use mqtt_messages::Codec;
+
+    for animation in animations() {
+        let command = Command::Animation(animation);
+        let data = command.encode_with(Codec::Json);
+        assert_eq!(
+            Command::decode_kind_with(Codec::Json, CommandKind::Animation, &data).unwrap(),
+            command
+        );
+    }
+}
+
+#[test]
+fn layout() {
+    let (buf, len) = encode(&animation(Pattern::Blink(BLUE)));
+    assert_eq!(buf[..len], [1, 0, 0, 0x03, 0xE8, 0, 3, 1, 0, 0, 255]);
+    let (buf, len) = encode(&animation(keyframes(&[0x0102])));
+    assert_eq!(
+        buf[..len],
+        [4, 0, 0, 0x03, 0xE8, 0, 3, 1, 1, 0, 0, 1, 2, 255, 0, 0]
+    );
+}
+
+#[test]
+fn wrong_length() {
+    for animation in animations() {
+        let (buf, len) = encode(&animation);
+        assert_exact_len(MessageType::Command, &buf[..len], decode);
+    }
+}
+
+#[test]
+fn invalid_pattern() {
+    let (mut buf, len) = encode(&animation(Pattern::Rainbow));
+    for code in [5, 0xFF] {
+        buf[0] = code;
+        assert!(matches!(
+            Animation::decode(&buf[..len]),
+            Err(ConvertError::InvalidData)
+        ));
+    }
+}
+
+#[test]
+fn too_many_keyframes() {
+    let (mut buf, _) = encode(&animation(keyframes(&[0])));
+    buf[8] = MAX_KEYFRAMES as u8 + 1;
+    assert!(matches!(
+        Animation::decode(&buf),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[test]
+fn unplayable() {
+    let zero_period = Animation {
+        period_ms: 0,
+        ..animation(Pattern::Solid(RED))
+    };
+    for animation in [
+        zero_period,
+        animation(keyframes(&[])),
+        animation(keyframes(&[100, 100])),
+        animation(keyframes(&[200, 100])),
+        animation(keyframes(&[0, 1_000])),
+    ] {
+        assert!(matches!(
+            animation.validate(),
+            Err(ConvertError::InvalidData)
+        ));
+        let (buf, len) = encode(&animation);
+        assert!(
+            matches!(
+                Animation::decode(&buf[..len]),
+                Err(ConvertError::InvalidData)
+            ),
+            "{:?}",
+            animation
+        );
+    }
+    for animation in animations() {
+        animation.validate().unwrap();
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_invalid() {
+    use mqtt_messages::Codec;
+
+    for data in [
+        // unplayable
+        &br#"{"animation":{"pattern":"rainbow","period_ms":0,"repeat":0,"priority":0}}"#[..],
+        br#"{"animation":{"pattern":{"keyframes":[]},"period_ms":10,"repeat":0,"priority":0}}"#,
+        br#"{"animation":{"pattern":{"keyframes":[{"at_ms":5,"color":{"r":1,"g":2,"b":3}},{"at_ms":1,"color":{"r":1,"g":2,"b":3}}]},"period_ms":10,"repeat":0,"priority":0}}"#,
+        br#"{"animation":{"pattern":{"keyframes":[{"at_ms":10,"color":{"r":1,"g":2,"b":3}}]},"period_ms":10,"repeat":0,"priority":0}}"#,
+    ] {
+        assert!(
+            matches!(
+                Command::decode_kind_with(Codec::Json, CommandKind::Animation, data),
+                Err(ConvertError::InvalidData)
+            ),
+            "{}",
+            String::from_utf8_lossy(data)
+        );
+    }
+    for data in [
+        // malformed
+        &br#"{"animation":{"pattern":"sparkle","period_ms":10,"repeat":0,"priority":0}}"#[..],
+        br#"{"animation":{"pattern":"rainbow","period_ms":10,"repeat":70000,"priority":0}}"#,
+        br#"{"animation":{"pattern":"rainbow","period_ms":10}}"#,
+    ] {
+        assert!(
+            matches!(
+                Command::decode_kind_with(Codec::Json, CommandKind::Animation, data),
+                Err(ConvertError::Json(_))
+            ),
+            "{}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn buffer_too_small() {
+    let mut buf = [0; 10];
+    assert!(matches!(
+        animation(Pattern::Solid(RED)).encode_into(&mut buf),
+        Err(ConvertError::BufferTooSmall(11))
+    ));
+}
+
+#[test]
+fn colors() {
+    let blink = animation(Pattern::Blink(BLUE));
+    assert_eq!(blink.color_at(0), Some(BLUE));
+    assert_eq!(blink.color_at(499), Some(BLUE));
+    assert_eq!(blink.color_at(500), Some(RGB8::default()));
+    assert_eq!(blink.color_at(1_000), Some(BLUE));
+    // three periods, then it is over
+    assert_eq!(blink.color_at(2_999), Some(RGB8::default()));
+    assert_eq!(blink.color_at(3_000), None);
+
+    let mut keyframes = Vec::new();
+    keyframes
+        .push(Keyframe::new(0, RGB8::new(0, 0, 0)))
+        .unwrap();
+    keyframes
+        .push(Keyframe::new(500, RGB8::new(200, 0, 0)))
+        .unwrap();
+    let fade = animation(Pattern::Keyframes(keyframes));
+    assert_eq!(fade.color_at(250), Some(RGB8::new(100, 0, 0)));
+    assert_eq!(fade.color_at(500), Some(RGB8::new(200, 0, 0)));
+    assert_eq!(fade.color_at(750), Some(RGB8::new(100, 0, 0)));
+}
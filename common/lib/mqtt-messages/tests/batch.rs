@@ -0,0 +1,156 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{enveloped, UUID};
+use mqtt_messages::batch::{batch_topic, is_batch_topic, Batcher};
+use mqtt_messages::envelope::{MessageType, HEADER_LEN};
+use mqtt_messages::{
+    Codec, ConvertError, Sample, Telemetry, TelemetryBatch, TelemetryKind, Vector3,
+};
+
+fn batch() -> TelemetryBatch {
+    TelemetryBatch {
+        samples: vec![
+            Sample {
+                timestamp_ms: 1_500,
+                telemetry: Telemetry::ChipTemperature(31.5),
+            },
+            Sample {
+                timestamp_ms: 1_000,
+                telemetry: Telemetry::Accel(Vector3::new(0.0, -1.0, 0.5)),
+            },
+            Sample {
+                timestamp_ms: 2_000,
+                telemetry: Telemetry::StepCount(42),
+            },
+        ],
+    }
+}
+
+fn decode(data: &[u8]) -> Result<TelemetryBatch, ConvertError> {
+    TelemetryBatch::decode_with(Codec::Binary, data)
+}
+
+#[test]
+fn round_trip() {
+    for batch in [batch(), TelemetryBatch::default()] {
+        assert_eq!(
+            decode(&batch.encode_with(Codec::Binary).unwrap()).unwrap(),
+            batch
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    let data = batch().encode_with(Codec::Json).unwrap();
+    assert_eq!(
+        TelemetryBatch::decode_with(Codec::Json, &data).unwrap(),
+        batch()
+    );
+}
+
+#[test]
+fn offsets_from_the_earliest_sample() {
+    let data = batch().encode_with(Codec::Binary).unwrap();
+    let body = &data[HEADER_LEN..];
+    assert_eq!(body[..8], 1_000u64.to_be_bytes());
+    // the first record is 500 ms after the earliest sample
+    assert_eq!(body[8..12], 500u32.to_be_bytes());
+    assert_eq!(body[12], MessageType::ChipTemperature as u8);
+    assert_eq!(body[13..17], 31.5f32.to_be_bytes());
+}
+
+#[test]
+fn span_too_long() {
+    let batch = TelemetryBatch {
+        samples: vec![
+            Sample {
+                timestamp_ms: 0,
+                telemetry: Telemetry::StepCount(1),
+            },
+            Sample {
+                timestamp_ms: u64::from(u32::MAX) + 1,
+                telemetry: Telemetry::StepCount(2),
+            },
+        ],
+    };
+    assert!(matches!(
+        batch.encode_with(Codec::Binary),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[test]
+fn cut_records() {
+    let data = batch().encode_with(Codec::Binary).unwrap();
+    let body = &data[HEADER_LEN..];
+    // without the whole base timestamp, a record header or a value
+    for len in [7, 8 + 3, 8 + 5 + 3, body.len() - 1] {
+        assert!(
+            matches!(
+                decode(&enveloped(MessageType::TelemetryBatch, &body[..len])),
+                Err(ConvertError::Length(_))
+            ),
+            "{} byte body",
+            len
+        );
+    }
+    let body = [body, &[0]].concat();
+    assert!(matches!(
+        decode(&enveloped(MessageType::TelemetryBatch, &body)),
+        Err(ConvertError::Length(_))
+    ));
+}
+
+#[test]
+fn invalid_sample_kind() {
+    // a known message type, but not telemetry
+    let mut body = [&[0; 8 + 4][..], &[MessageType::Color as u8], &[0; 4]].concat();
+    assert!(matches!(
+        decode(&enveloped(MessageType::TelemetryBatch, &body)),
+        Err(ConvertError::UnexpectedMessageType(MessageType::Color))
+    ));
+
+    body[12] = 0xFF;
+    assert!(matches!(
+        decode(&enveloped(MessageType::TelemetryBatch, &body)),
+        Err(ConvertError::UnknownMessageType(0xFF))
+    ));
+}
+
+#[test]
+fn timestamp_overflow() {
+    let mut body = u64::MAX.to_be_bytes().to_vec();
+    body.extend_from_slice(&1u32.to_be_bytes());
+    body.push(MessageType::StepCount as u8);
+    body.extend_from_slice(&7u32.to_be_bytes());
+    assert!(matches!(
+        decode(&enveloped(MessageType::TelemetryBatch, &body)),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[test]
+fn batcher_flushes_stale_samples() {
+    let mut batcher = Batcher::new(10, 5_000);
+    assert!(batcher.flush_stale(0).is_none());
+    assert!(batcher.push(1_000, Telemetry::StepCount(1)).is_none());
+    assert!(batcher.flush_stale(5_999).is_none());
+    assert_eq!(batcher.flush_stale(6_000).unwrap().samples.len(), 1);
+    assert!(batcher.flush().is_none());
+}
+
+#[test]
+fn topics() {
+    assert_eq!(
+        batch_topic(UUID),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/sensor_data/batch"
+    );
+    assert!(is_batch_topic(UUID, &batch_topic(UUID)));
+    assert!(!is_batch_topic(UUID, &TelemetryKind::Humidity.topic(UUID)));
+    assert!(!is_batch_topic("other", &batch_topic(UUID)));
+    assert_eq!(TelemetryKind::from_topic(UUID, &batch_topic(UUID)), None);
+}
@@ -0,0 +1,180 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{assert_exact_len, enveloped, UUID};
+use mqtt_messages::envelope::{MessageType, HEADER_LEN};
+use mqtt_messages::{
+    hello_topic, Capabilities, Codec, CommandKind, ConvertError, Sensor, TelemetryKind,
+};
+
+fn capabilities() -> Capabilities {
+    Capabilities {
+        firmware_version: "0.2.0".to_owned(),
+        board_revision: "c3-dkc02".to_owned(),
+        codec: Codec::Binary,
+        commands: CommandKind::ALL.to_vec(),
+        sensors: Sensor::ALL.to_vec(),
+        telemetry: TelemetryKind::ALL.to_vec(),
+    }
+}
+
+fn minimal() -> Capabilities {
+    Capabilities {
+        firmware_version: String::new(),
+        board_revision: String::new(),
+        codec: Codec::Binary,
+        commands: vec![CommandKind::BoardLed],
+        sensors: vec![],
+        telemetry: vec![],
+    }
+}
+
+fn decode(data: &[u8]) -> Result<Capabilities, ConvertError> {
+    Capabilities::decode_with(Codec::Binary, data)
+}
+
+fn decode_body(body: &[u8]) -> Result<Capabilities, ConvertError> {
+    decode(&enveloped(MessageType::Capabilities, body))
+}
+
+#[test]
+fn round_trip() {
+    for capabilities in [capabilities(), minimal()] {
+        let data = capabilities.encode_with(Codec::Binary).unwrap();
+        assert_eq!(decode(&data).unwrap(), capabilities);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    let json = Capabilities {
+        codec: Codec::Json,
+        ..capabilities()
+    };
+    for capabilities in [capabilities(), minimal(), json.clone()] {
+        let data = capabilities.encode_with(Codec::Json).unwrap();
+        assert_eq!(
+            Capabilities::decode_with(Codec::Json, &data).unwrap(),
+            capabilities
+        );
+    }
+    // the advertised codec is independent of the one used for the hello
+    assert_eq!(
+        decode(&json.encode_with(Codec::Binary).unwrap()).unwrap(),
+        json
+    );
+}
+
+#[test]
+fn layout() {
+    let data = minimal().encode_with(Codec::Binary).unwrap();
+    assert_eq!(data[HEADER_LEN..], [0, 0, 0, 1, 0, 0, 0]);
+    let data = capabilities().encode_with(Codec::Binary).unwrap();
+    assert_eq!(data[HEADER_LEN..HEADER_LEN + 6], *b"\x050.2.0");
+}
+
+#[test]
+fn long_strings_are_cut_at_a_char_boundary() {
+    let capabilities = Capabilities {
+        // 2 bytes per char, 256 bytes in all
+        firmware_version: "ä".repeat(128),
+        ..minimal()
+    };
+    let decoded = decode(&capabilities.encode_with(Codec::Binary).unwrap()).unwrap();
+    assert_eq!(decoded.firmware_version, "ä".repeat(127));
+}
+
+#[test]
+fn long_lists_are_an_error() {
+    let capabilities = Capabilities {
+        telemetry: vec![TelemetryKind::Humidity; 256],
+        ..minimal()
+    };
+    assert!(matches!(
+        capabilities.encode_with(Codec::Binary),
+        Err(ConvertError::Length(256))
+    ));
+
+    let capabilities = Capabilities {
+        telemetry: vec![TelemetryKind::Humidity; 255],
+        ..minimal()
+    };
+    let decoded = decode(&capabilities.encode_with(Codec::Binary).unwrap()).unwrap();
+    assert_eq!(decoded, capabilities);
+}
+
+#[test]
+fn wrong_length() {
+    let data = capabilities().encode_with(Codec::Binary).unwrap();
+    assert_exact_len(MessageType::Capabilities, &data[HEADER_LEN..], decode);
+    // boards with older firmware say hello without a payload
+    assert!(matches!(decode(&[]), Err(ConvertError::Length(0))));
+}
+
+#[test]
+fn invalid_codes() {
+    // codec
+    assert!(matches!(
+        decode_body(&[0, 0, 2, 0, 0, 0]),
+        Err(ConvertError::InvalidData)
+    ));
+    // command
+    assert!(matches!(
+        decode_body(&[0, 0, 0, 1, 0xFF, 0, 0]),
+        Err(ConvertError::InvalidData)
+    ));
+    // sensor
+    assert!(matches!(
+        decode_body(&[0, 0, 0, 0, 1, 3, 0]),
+        Err(ConvertError::InvalidData)
+    ));
+    // telemetry that is not a known message type, or not a reading
+    assert!(matches!(
+        decode_body(&[0, 0, 0, 0, 0, 1, 0xEE]),
+        Err(ConvertError::UnknownMessageType(0xEE))
+    ));
+    assert!(matches!(
+        decode_body(&[0, 0, 0, 0, 0, 1, MessageType::Color as u8]),
+        Err(ConvertError::UnexpectedMessageType(MessageType::Color))
+    ));
+    // firmware version
+    assert!(matches!(
+        decode_body(&[1, 0xFF, 0, 0, 0, 0, 0]),
+        Err(ConvertError::InvalidData)
+    ));
+    for code in 0..=u8::MAX {
+        if let Ok(sensor) = Sensor::try_from(code) {
+            assert_eq!(sensor as u8, code);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_invalid() {
+    for data in [
+        &br#"{"firmware_version":"0.2.0","board_revision":"","codec":"xml","commands":[],"sensors":[],"telemetry":[]}"#[..],
+        br#"{"firmware_version":"0.2.0","board_revision":"","codec":"json","commands":["reboot"],"sensors":[],"telemetry":[]}"#,
+        br#"{"firmware_version":"0.2.0","board_revision":"","codec":"json","commands":[],"sensors":["bme280"],"telemetry":[]}"#,
+        br#"{"firmware_version":"0.2.0","board_revision":"","codec":"json","commands":[],"sensors":[]}"#,
+    ] {
+        assert!(
+            matches!(
+                Capabilities::decode_with(Codec::Json, data),
+                Err(ConvertError::Json(_))
+            ),
+            "{}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn queries() {
+    assert!(minimal().supports(CommandKind::BoardLed));
+    assert!(capabilities().publishes(TelemetryKind::StepCount));
+    assert!(!minimal().publishes(TelemetryKind::StepCount));
+    assert_eq!(minimal().topic(UUID), hello_topic(UUID));
+}
@@ -0,0 +1,58 @@
+//! Helpers shared by the payload tests
+//!
+//! The envelope itself is covered by `tests/envelope.rs`, these only check
+//! that each message insists on the body length its format defines.
+
+#![allow(dead_code)]
+
+use std::fmt::Debug;
+
+use mqtt_messages::envelope::{Envelope, MessageType};
+use mqtt_messages::ConvertError;
+
+pub const UUID: &str = "6188eec9-6d3a-4eac-996f-ac4ab13f312d";
+
+/// `body` behind a current version header
+pub fn enveloped(message_type: MessageType, body: &[u8]) -> Vec<u8> {
+    let envelope = Envelope::new(message_type, body);
+    let mut data = vec![0; envelope.encoded_len()];
+    envelope.encode_into(&mut data).unwrap();
+    data
+}
+
+/// Checks that `decode` rejects `body` without its last byte
+pub fn assert_too_short<T: Debug>(
+    message_type: MessageType,
+    body: &[u8],
+    decode: impl Fn(&[u8]) -> Result<T, ConvertError>,
+) {
+    let short = &body[..body.len() - 1];
+    let result = decode(&enveloped(message_type, short));
+    assert!(
+        matches!(result, Err(ConvertError::Length(_))),
+        "{} byte {:?} body: {:?}",
+        short.len(),
+        message_type,
+        result
+    );
+}
+
+/// Checks that `decode` rejects `body` both without its last byte and with
+/// one more byte
+pub fn assert_exact_len<T: Debug>(
+    message_type: MessageType,
+    body: &[u8],
+    decode: impl Fn(&[u8]) -> Result<T, ConvertError>,
+) {
+    assert_too_short(message_type, body, &decode);
+
+    let long = [body, &[0]].concat();
+    let result = decode(&enveloped(message_type, &long));
+    assert!(
+        matches!(result, Err(ConvertError::Length(_))),
+        "{} byte {:?} body: {:?}",
+        long.len(),
+        message_type,
+        result
+    );
+}
@@ -0,0 +1,218 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{assert_exact_len, enveloped, UUID};
+use mqtt_messages::config::config_filter;
+use mqtt_messages::envelope::{MessageType, HEADER_LEN};
+use mqtt_messages::{Codec, Command, CommandKind, ConfigKey, ConfigValue, ConvertError, Settings};
+
+fn values() -> Vec<ConfigValue> {
+    ConfigKey::ALL
+        .into_iter()
+        .map(|key| ConfigValue {
+            key,
+            value: *key.range().end(),
+        })
+        .chain([ConfigValue {
+            key: ConfigKey::PublishInterval,
+            value: u32::MAX,
+        }])
+        .collect()
+}
+
+fn commands() -> Vec<Command> {
+    ConfigKey::ALL
+        .into_iter()
+        .flat_map(|key| {
+            [
+                Command::ConfigGet { key },
+                Command::ConfigSet {
+                    key,
+                    value: 0x0102_0304,
+                },
+            ]
+        })
+        .collect()
+}
+
+#[test]
+fn value_round_trip() {
+    for value in values() {
+        let data = value.encode_with(Codec::Binary);
+        assert_eq!(ConfigValue::decode(&data).unwrap(), value);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn value_json_round_trip() {
+    for value in values() {
+        let data = value.encode_with(Codec::Json);
+        assert_eq!(ConfigValue::decode_with(Codec::Json, &data).unwrap(), value);
+    }
+    let value = ConfigValue {
+        key: ConfigKey::MqttQos,
+        value: 2,
+    };
+    assert_eq!(
+        value.encode_with(Codec::Json),
+        br#"{"key":"mqtt_qos","value":2}"#
+    );
+}
+
+#[test]
+fn value_layout() {
+    let value = ConfigValue {
+        key: ConfigKey::LedBrightness,
+        value: 0x0102_0304,
+    };
+    let data = value.encode_with(Codec::Binary);
+    assert_eq!(data[1], MessageType::ConfigValue as u8);
+    assert_eq!(data[HEADER_LEN..], [1, 1, 2, 3, 4]);
+}
+
+#[test]
+fn value_wrong_length() {
+    assert_exact_len(
+        MessageType::ConfigValue,
+        &[0, 0, 0, 0, 1],
+        ConfigValue::decode,
+    );
+    // unlike `ConfigSet`, a value is always enveloped
+    assert!(matches!(
+        ConfigValue::decode(&[0, 0, 0, 0, 1]),
+        Err(ConvertError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn value_invalid_key() {
+    for key in [3, 0xFF] {
+        let data = enveloped(MessageType::ConfigValue, &[key, 0, 0, 0, 1]);
+        assert!(matches!(
+            ConfigValue::decode(&data),
+            Err(ConvertError::InvalidData)
+        ));
+    }
+    for code in 0..=u8::MAX {
+        if let Ok(key) = ConfigKey::try_from(code) {
+            assert_eq!(key as u8, code);
+        }
+    }
+}
+
+#[test]
+fn command_round_trip() {
+    for command in commands() {
+        let data = command.encode_with(Codec::Binary);
+        assert_eq!(
+            Command::decode_kind_with(Codec::Binary, command.kind(), &data).unwrap(),
+            command
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn command_json_round_trip() {
+    for command in commands() {
+        let data = command.encode_with(Codec::Json);
+        assert_eq!(
+            Command::decode_kind_with(Codec::Json, command.kind(), &data).unwrap(),
+            command
+        );
+    }
+}
+
+#[test]
+fn command_wrong_length() {
+    // bare, a `ConfigGet` is one byte and a `ConfigSet` five
+    assert!(matches!(
+        Command::decode_kind(CommandKind::ConfigGet, &[]),
+        Err(ConvertError::Length(0))
+    ));
+    assert_exact_len(MessageType::Command, &[0, 0, 0, 0, 1], |data| {
+        Command::decode_kind(CommandKind::ConfigSet, data)
+    });
+    let data = enveloped(MessageType::Command, &[0, 0]);
+    assert!(matches!(
+        Command::decode_kind(CommandKind::ConfigGet, &data),
+        Err(ConvertError::Length(2))
+    ));
+}
+
+#[test]
+fn command_invalid_key() {
+    assert!(matches!(
+        Command::decode_kind(CommandKind::ConfigGet, &[3]),
+        Err(ConvertError::InvalidData)
+    ));
+    assert!(matches!(
+        Command::decode_kind(CommandKind::ConfigSet, &[0xFF, 0, 0, 0, 1]),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_invalid() {
+    for data in [
+        &br#"{"key":"volume","value":1}"#[..],
+        br#"{"key":"mqtt_qos","value":-1}"#,
+        br#"{"key":"mqtt_qos"}"#,
+    ] {
+        assert!(matches!(
+            ConfigValue::decode_with(Codec::Json, data),
+            Err(ConvertError::Json(_))
+        ));
+    }
+    assert!(matches!(
+        Command::decode_kind_with(
+            Codec::Json,
+            CommandKind::ConfigSet,
+            br#"{"config_set":{"key":"volume","value":1}}"#
+        ),
+        Err(ConvertError::Json(_))
+    ));
+}
+
+#[test]
+fn settings() {
+    let mut settings = Settings::default();
+    for key in ConfigKey::ALL {
+        let range = key.range();
+        settings.set(key, *range.start()).unwrap();
+        assert_eq!(settings.get(key), *range.start());
+        settings.set(key, *range.end()).unwrap();
+        assert_eq!(settings.value(key).value, *range.end());
+        assert!(matches!(
+            settings.set(key, *range.end() + 1),
+            Err(ConvertError::InvalidData)
+        ));
+        assert_eq!(settings.get(key), *range.end(), "unchanged");
+    }
+    assert!(matches!(
+        settings.set(ConfigKey::PublishInterval, 99),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[test]
+fn topics() {
+    for key in ConfigKey::ALL {
+        assert_eq!(ConfigKey::from_topic(UUID, &key.topic(UUID)), Some(key));
+    }
+    assert_eq!(
+        ConfigKey::LedBrightness.topic(UUID),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/config/led_brightness"
+    );
+    assert_eq!(
+        config_filter(UUID),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/config/+"
+    );
+    assert_eq!(
+        ConfigKey::from_topic(UUID, &format!("{}/config/volume", UUID)),
+        None
+    );
+}
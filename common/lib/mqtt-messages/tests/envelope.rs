@@ -0,0 +1,196 @@
+use mqtt_messages::envelope::{Envelope, MessageType, HEADER_LEN, MAGIC, MAX_BODY_LEN, VERSION};
+use mqtt_messages::ConvertError;
+
+#[test]
+fn round_trip() {
+    let envelope = Envelope::new(MessageType::Color, &[1, 2, 3]);
+    let mut buf = [0; HEADER_LEN + 3];
+    assert_eq!(envelope.encode_into(&mut buf).unwrap(), buf.len());
+    assert_eq!(buf, [MAGIC | VERSION, 0x01, 0, 3, 1, 2, 3]);
+    assert_eq!(Envelope::decode(&buf).unwrap(), envelope);
+}
+
+#[test]
+fn empty_body() {
+    let envelope = Envelope::new(MessageType::Command, &[]);
+    let mut buf = [0; HEADER_LEN];
+    envelope.encode_into(&mut buf).unwrap();
+    assert_eq!(Envelope::decode(&buf).unwrap(), envelope);
+}
+
+#[test]
+fn buffer_too_small() {
+    let envelope = Envelope::new(MessageType::Color, &[1, 2, 3]);
+    let mut buf = [0; HEADER_LEN + 2];
+    assert!(matches!(
+        envelope.encode_into(&mut buf),
+        Err(ConvertError::BufferTooSmall(7))
+    ));
+}
+
+#[test]
+fn longest_body() {
+    let body = vec![0xAA; MAX_BODY_LEN];
+    let mut buf = vec![0; HEADER_LEN + MAX_BODY_LEN];
+    let envelope = Envelope::new(MessageType::TelemetryBatch, &body);
+    envelope.encode_into(&mut buf).unwrap();
+    assert_eq!(Envelope::decode(&buf).unwrap().body.len(), MAX_BODY_LEN);
+}
+
+#[test]
+fn body_too_long() {
+    let body = vec![0; MAX_BODY_LEN + 1];
+    let mut buf = vec![0; HEADER_LEN + body.len()];
+    let envelope = Envelope::new(MessageType::TelemetryBatch, &body);
+    assert!(matches!(
+        envelope.encode_into(&mut buf),
+        Err(ConvertError::Length(len)) if len == MAX_BODY_LEN + 1
+    ));
+    assert!(matches!(
+        Envelope::header(MessageType::Signed, MAX_BODY_LEN + 1),
+        Err(ConvertError::Length(_))
+    ));
+    assert_eq!(buf[0], 0, "nothing is written");
+}
+
+#[test]
+fn legacy_v0() {
+    let legacy = [10, 20, 30];
+    let envelope = Envelope::decode_or_legacy(&legacy, MessageType::Color, 3).unwrap();
+    assert_eq!(envelope.version, 0);
+    assert_eq!(envelope.message_type, MessageType::Color);
+    assert_eq!(envelope.body, legacy);
+}
+
+#[test]
+fn legacy_length_is_not_a_header() {
+    // a legacy color that happens to look like a header is still a color
+    let legacy = [MAGIC | VERSION, 0x02, 0];
+    let envelope = Envelope::decode_or_legacy(&legacy, MessageType::Color, 3).unwrap();
+    assert_eq!(envelope.version, 0);
+    assert_eq!(envelope.body, legacy);
+}
+
+#[test]
+fn wrapped_instead_of_legacy() {
+    let data = [MAGIC | VERSION, 0x01, 0, 3, 1, 2, 3];
+    let envelope = Envelope::decode_or_legacy(&data, MessageType::Color, 3).unwrap();
+    assert_eq!(envelope.version, VERSION);
+    assert_eq!(envelope.body, [1, 2, 3]);
+}
+
+#[test]
+fn unexpected_type() {
+    let data = [MAGIC | VERSION, 0x02, 0, 3, 1, 2, 3];
+    assert!(matches!(
+        Envelope::decode_or_legacy(&data, MessageType::Color, 3),
+        Err(ConvertError::UnexpectedMessageType(MessageType::Command))
+    ));
+}
+
+#[test]
+fn too_short_for_a_header() {
+    assert!(matches!(
+        Envelope::decode(&[MAGIC | VERSION, 0x01, 0]),
+        Err(ConvertError::Length(3))
+    ));
+    assert!(matches!(
+        Envelope::decode(&[]),
+        Err(ConvertError::Length(0))
+    ));
+}
+
+#[test]
+fn wrong_magic() {
+    assert!(matches!(
+        Envelope::decode(&[0x01, 0x01, 0, 0]),
+        Err(ConvertError::InvalidHeader)
+    ));
+    assert!(matches!(
+        Envelope::decode(&[0xF1, 0x01, 0, 0]),
+        Err(ConvertError::InvalidHeader)
+    ));
+}
+
+#[test]
+fn future_version() {
+    assert!(matches!(
+        Envelope::decode(&[MAGIC | (VERSION + 1), 0x01, 0, 0]),
+        Err(ConvertError::UnsupportedVersion(v)) if v == VERSION + 1
+    ));
+    assert!(matches!(
+        Envelope::decode(&[MAGIC, 0x01, 0, 0]),
+        Err(ConvertError::UnsupportedVersion(0))
+    ));
+}
+
+#[test]
+fn unknown_type() {
+    assert!(matches!(
+        Envelope::decode(&[MAGIC | VERSION, 0xFF, 0, 0]),
+        Err(ConvertError::UnknownMessageType(0xFF))
+    ));
+}
+
+#[test]
+fn length_mismatch() {
+    // header says 3 bytes, 2 follow
+    assert!(matches!(
+        Envelope::decode(&[MAGIC | VERSION, 0x01, 0, 3, 1, 2]),
+        Err(ConvertError::Length(2))
+    ));
+    // header says 1 byte, 2 follow
+    assert!(matches!(
+        Envelope::decode(&[MAGIC | VERSION, 0x01, 0, 1, 1, 2]),
+        Err(ConvertError::Length(2))
+    ));
+}
+
+#[test]
+fn every_message_type_round_trips() {
+    for code in 0..=u8::MAX {
+        if let Ok(message_type) = MessageType::try_from(code) {
+            assert_eq!(message_type as u8, code);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn colors() {
+    use mqtt_messages::ColorData;
+
+    let color = ColorData::BoardLed([1, 2, 3].into());
+    for data in [color.data().to_vec(), color.enveloped()] {
+        let ColorData::BoardLed(rgb) = ColorData::try_from(&data[..]).unwrap();
+        assert_eq!(rgb, [1, 2, 3].into());
+    }
+
+    let command = Envelope::new(MessageType::Command, &[1, 2, 3])
+        .encode()
+        .unwrap();
+    assert!(matches!(
+        ColorData::try_from(&command[..]),
+        Err(ConvertError::UnexpectedMessageType(MessageType::Command))
+    ));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn commands() {
+    use mqtt_messages::{Command, RawCommandData};
+
+    for data in [
+        vec![1, 2, 3],
+        Envelope::new(MessageType::Command, &[1, 2, 3])
+            .encode()
+            .unwrap(),
+    ] {
+        let raw = RawCommandData {
+            path: "",
+            data: data.into(),
+        };
+        let command = Command::try_from(raw).unwrap();
+        assert_eq!(command, Command::BoardLed([1, 2, 3].into()));
+    }
+}
@@ -0,0 +1,93 @@
+#![cfg(feature = "serde")]
+
+mod common;
+
+use common::UUID;
+use mqtt_messages::home_assistant::{board_led_light, discoveries, temperature_sensor, Discovery};
+use mqtt_messages::{
+    color_topic, status_topic, temperature_data_topic, Codec, ColorData, TopicScheme,
+};
+use serde_json::Value;
+
+fn config(discovery: &Discovery) -> Value {
+    serde_json::from_slice(&discovery.payload).unwrap()
+}
+
+#[test]
+fn topics() {
+    let topics: Vec<_> = discoveries(&TopicScheme::BARE, UUID)
+        .into_iter()
+        .map(|d| d.topic)
+        .collect();
+    assert_eq!(
+        topics,
+        [
+            "homeassistant/sensor/6188eec9-6d3a-4eac-996f-ac4ab13f312d/temperature/config",
+            "homeassistant/light/6188eec9-6d3a-4eac-996f-ac4ab13f312d/board_led/config",
+        ]
+    );
+}
+
+#[test]
+fn temperature_sensor_config() {
+    let config = config(&temperature_sensor(&TopicScheme::BARE, UUID));
+    assert_eq!(config["state_topic"], temperature_data_topic(UUID));
+    assert_eq!(config["unique_id"], format!("{}_temperature", UUID));
+    assert_eq!(config["device_class"], "temperature");
+    assert_eq!(config["unit_of_measurement"], "°C");
+    assert_eq!(config["value_template"], "{{ value_json.value }}");
+}
+
+#[test]
+fn board_led_light_config() {
+    let config = config(&board_led_light(&TopicScheme::BARE, UUID));
+    assert_eq!(config["command_topic"], color_topic(UUID));
+    assert_eq!(config["unique_id"], format!("{}_board_led", UUID));
+    assert_eq!(config["schema"], "template");
+    assert_eq!(config["optimistic"], true);
+
+    // what the board receives when the light is turned off
+    let off = config["command_off_template"].as_str().unwrap();
+    let ColorData::BoardLed(rgb) = ColorData::decode_with(Codec::Json, off.as_bytes()).unwrap();
+    assert_eq!(rgb, [0, 0, 0].into());
+}
+
+#[test]
+fn one_device() {
+    for discovery in discoveries(&TopicScheme::BARE, UUID) {
+        let device = &config(&discovery)["device"];
+        assert_eq!(device["identifiers"], serde_json::json!([UUID]));
+        assert_eq!(device["model"], "ESP32-C3-DevKitC-02");
+    }
+}
+
+#[test]
+fn offline_with_the_board() {
+    for discovery in discoveries(&TopicScheme::BARE, UUID) {
+        let availability = &config(&discovery)["availability"][0];
+        assert_eq!(availability["topic"], status_topic(UUID));
+        assert_eq!(availability["value_template"], "{{ value_json.state }}");
+    }
+}
+
+#[test]
+fn prefixed_topics() {
+    let scheme = TopicScheme::new("org/site").unwrap();
+    let root = scheme.root(UUID);
+
+    let sensor = temperature_sensor(&scheme, UUID);
+    assert_eq!(
+        config(&sensor)["state_topic"],
+        temperature_data_topic(&root)
+    );
+    let light = config(&board_led_light(&scheme, UUID));
+    assert_eq!(light["command_topic"], color_topic(&root));
+    assert_eq!(light["availability"][0]["topic"], status_topic(&root));
+
+    // discovery itself stays where Home Assistant looks for it, keyed by UUID
+    assert_eq!(
+        sensor.topic,
+        temperature_sensor(&TopicScheme::BARE, UUID).topic
+    );
+    assert_eq!(light["unique_id"], format!("{}_board_led", UUID));
+}
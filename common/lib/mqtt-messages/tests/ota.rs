@@ -0,0 +1,258 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{assert_exact_len, assert_too_short, enveloped, UUID};
+use mqtt_messages::envelope::{MessageType, HEADER_LEN};
+use mqtt_messages::ota::{ota_status_topic, ota_topic, parse_digest, write_digest, DIGEST_LEN};
+use mqtt_messages::{Codec, ConvertError, OtaReport, OtaStart};
+
+const DIGEST_HEX: &str = "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855";
+
+fn digest() -> [u8; DIGEST_LEN] {
+    parse_digest(DIGEST_HEX).unwrap()
+}
+
+fn start() -> OtaStart {
+    OtaStart {
+        url: "https://example.com/firmware-0.2.0.bin".to_owned(),
+        sha256: digest(),
+        version: "0.2.0".to_owned(),
+    }
+}
+
+fn reports() -> Vec<OtaReport> {
+    vec![
+        OtaReport::Progress {
+            received: 4096,
+            total: 917_504,
+        },
+        OtaReport::Progress {
+            received: u32::MAX,
+            total: 0,
+        },
+        OtaReport::Done {
+            version: "0.2.0".to_owned(),
+        },
+        OtaReport::Failed {
+            reason: "digest mismatch".to_owned(),
+        },
+        OtaReport::Failed {
+            reason: String::new(),
+        },
+    ]
+}
+
+fn decode_start(body: &[u8]) -> Result<OtaStart, ConvertError> {
+    OtaStart::decode_with(Codec::Binary, &enveloped(MessageType::OtaStart, body))
+}
+
+fn decode_report(body: &[u8]) -> Result<OtaReport, ConvertError> {
+    OtaReport::decode_with(Codec::Binary, &enveloped(MessageType::OtaReport, body))
+}
+
+#[test]
+fn start_round_trip() {
+    let empty_version = OtaStart {
+        version: String::new(),
+        ..start()
+    };
+    for start in [start(), empty_version] {
+        let data = start.encode_with(Codec::Binary).unwrap();
+        assert_eq!(OtaStart::decode_with(Codec::Binary, &data).unwrap(), start);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn start_json_round_trip() {
+    let data = start().encode_with(Codec::Json).unwrap();
+    assert_eq!(OtaStart::decode_with(Codec::Json, &data).unwrap(), start());
+    let json = String::from_utf8(data).unwrap();
+    assert!(json.contains(&format!(r#""sha256":"{}""#, DIGEST_HEX)));
+}
+
+#[test]
+fn start_layout() {
+    let data = start().encode_with(Codec::Binary).unwrap();
+    let body = &data[HEADER_LEN..];
+    assert_eq!(body[..DIGEST_LEN], digest());
+    assert_eq!(body[DIGEST_LEN], 5);
+    assert_eq!(&body[DIGEST_LEN + 1..DIGEST_LEN + 6], b"0.2.0");
+    assert_eq!(&body[DIGEST_LEN + 6..], start().url.as_bytes());
+}
+
+#[test]
+fn long_versions_are_cut_at_a_char_boundary() {
+    let start = OtaStart {
+        // 2 bytes per char, 256 bytes in all
+        version: "ä".repeat(128),
+        ..start()
+    };
+    let data = start.encode_with(Codec::Binary).unwrap();
+    let decoded = OtaStart::decode_with(Codec::Binary, &data).unwrap();
+    assert_eq!(decoded.version, "ä".repeat(127));
+    assert_eq!(decoded.url, start.url);
+}
+
+#[test]
+fn start_wrong_length() {
+    let data = start().encode_with(Codec::Binary).unwrap();
+    let body = &data[HEADER_LEN..];
+    // the URL takes up the rest, so only the digest and version are checked
+    for end in [DIGEST_LEN, DIGEST_LEN + 1, DIGEST_LEN + 6] {
+        assert_too_short(MessageType::OtaStart, &body[..end], |data| {
+            OtaStart::decode_with(Codec::Binary, data)
+        });
+    }
+    assert!(matches!(decode_start(&[]), Err(ConvertError::Length(0))));
+}
+
+#[test]
+fn start_invalid_utf8() {
+    let mut body = digest().to_vec();
+    body.extend_from_slice(&[1, 0xFF]);
+    body.extend_from_slice(b"https://example.com");
+    assert!(matches!(
+        decode_start(&body),
+        Err(ConvertError::InvalidData)
+    ));
+
+    let mut body = digest().to_vec();
+    body.extend_from_slice(&[0, 0xFF]);
+    assert!(matches!(
+        decode_start(&body),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn start_json_invalid() {
+    for digest in [
+        "",
+        "e3b0",
+        &DIGEST_HEX.replace('e', "g"),
+        &format!("{}00", DIGEST_HEX),
+    ] {
+        let data = format!(
+            r#"{{"url":"https://example.com/fw.bin","sha256":"{}","version":"0.2.0"}}"#,
+            digest
+        );
+        assert!(
+            matches!(
+                OtaStart::decode_with(Codec::Json, data.as_bytes()),
+                Err(ConvertError::Json(_))
+            ),
+            "{}",
+            digest
+        );
+    }
+    assert!(matches!(
+        OtaStart::decode_with(Codec::Json, br#"{"url":"https://example.com/fw.bin"}"#),
+        Err(ConvertError::Json(_))
+    ));
+}
+
+#[test]
+fn report_round_trip() {
+    for report in reports() {
+        let data = report.encode_with(Codec::Binary).unwrap();
+        assert_eq!(
+            OtaReport::decode_with(Codec::Binary, &data).unwrap(),
+            report
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn report_json_round_trip() {
+    for report in reports() {
+        let data = report.encode_with(Codec::Json).unwrap();
+        assert_eq!(OtaReport::decode_with(Codec::Json, &data).unwrap(), report);
+    }
+    assert_eq!(
+        reports()[0].encode_with(Codec::Json).unwrap(),
+        br#"{"state":"progress","received":4096,"total":917504}"#
+    );
+}
+
+#[test]
+fn report_wrong_length() {
+    let progress = [0, 0, 0, 0x10, 0, 0, 0x0E, 0, 0];
+    assert_exact_len(MessageType::OtaReport, &progress, |data| {
+        OtaReport::decode_with(Codec::Binary, data)
+    });
+    assert!(matches!(decode_report(&[]), Err(ConvertError::InvalidData)));
+}
+
+#[test]
+fn report_invalid_state() {
+    for state in [3, 0xFF] {
+        assert!(matches!(
+            decode_report(&[state, b'x']),
+            Err(ConvertError::InvalidData)
+        ));
+    }
+}
+
+#[test]
+fn report_invalid_utf8() {
+    assert!(matches!(
+        decode_report(&[1, 0xFF]),
+        Err(ConvertError::InvalidData)
+    ));
+    assert!(matches!(
+        decode_report(&[2, 0xC3]),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn report_json_invalid() {
+    for data in [
+        &br#"{"state":"paused"}"#[..],
+        br#"{"state":"progress","received":-1,"total":0}"#,
+        br#"{"state":"done"}"#,
+    ] {
+        assert!(
+            matches!(
+                OtaReport::decode_with(Codec::Json, data),
+                Err(ConvertError::Json(_))
+            ),
+            "{}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn digests() {
+    let mut hex = String::new();
+    write_digest(&mut hex, &digest()).unwrap();
+    assert_eq!(hex, DIGEST_HEX);
+    assert_eq!(parse_digest(&DIGEST_HEX.to_uppercase()).unwrap(), digest());
+    assert!(matches!(
+        parse_digest(&DIGEST_HEX[1..]),
+        Err(ConvertError::Length(63))
+    ));
+    assert!(matches!(
+        parse_digest(&DIGEST_HEX.replace('e', "g")),
+        Err(ConvertError::InvalidData)
+    ));
+    // multi-byte chars must not be split into nibbles
+    assert!(parse_digest(&format!("ä{}", &DIGEST_HEX[2..])).is_err());
+}
+
+#[test]
+fn topics() {
+    assert_eq!(ota_topic(UUID), "6188eec9-6d3a-4eac-996f-ac4ab13f312d/ota");
+    assert_eq!(
+        ota_status_topic(UUID),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/ota/status"
+    );
+    assert_eq!(start().topic(UUID), ota_topic(UUID));
+    assert_eq!(reports()[0].topic(UUID), ota_status_topic(UUID));
+}
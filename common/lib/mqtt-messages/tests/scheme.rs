@@ -0,0 +1,82 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::UUID;
+use mqtt_messages::{hello_topic, CommandKind, ConvertError, Target, TopicScheme};
+
+#[test]
+fn valid_prefixes() {
+    for prefix in ["org", "org/site/esp-rs", "a b/ü"] {
+        let scheme = TopicScheme::new(prefix).unwrap();
+        assert_eq!(scheme.prefix(), Some(prefix));
+    }
+    assert_eq!(TopicScheme::new("").unwrap(), TopicScheme::BARE);
+}
+
+#[test]
+fn invalid_prefixes() {
+    for prefix in [
+        "$SYS",
+        "$share/group",
+        "org/+",
+        "org/#",
+        "+",
+        "/org",
+        "org/",
+        "org//site",
+        "/",
+        "org/\0",
+    ] {
+        assert!(
+            matches!(TopicScheme::new(prefix), Err(ConvertError::InvalidPath)),
+            "{:?}",
+            prefix
+        );
+    }
+}
+
+#[test]
+fn roots() {
+    assert_eq!(TopicScheme::BARE.root(UUID), UUID);
+    let scheme = TopicScheme::new("org/site").unwrap();
+    assert_eq!(
+        scheme.root(UUID),
+        "org/site/6188eec9-6d3a-4eac-996f-ac4ab13f312d"
+    );
+    assert_eq!(scheme.filter("+/hello"), "org/site/+/hello");
+}
+
+#[test]
+fn strip_and_uuid() {
+    let scheme = TopicScheme::new("org/site").unwrap();
+    let topic = hello_topic(&scheme.root(UUID));
+    assert_eq!(scheme.strip(&topic), Some(hello_topic(UUID).as_str()));
+    assert_eq!(scheme.uuid(&topic), Some(UUID));
+
+    let bare = hello_topic(UUID);
+    assert_eq!(TopicScheme::BARE.strip(&bare), Some(bare.as_str()));
+    assert_eq!(TopicScheme::BARE.uuid(&bare), Some(UUID));
+}
+
+#[test]
+fn topics_outside_the_prefix() {
+    let scheme = TopicScheme::new("site").unwrap();
+    for topic in [
+        // starts with the prefix string, but not with its level
+        format!("site2/{}/hello", UUID),
+        format!("sitex/{}/hello", UUID),
+        format!("other/site/{}/hello", UUID),
+        hello_topic(UUID),
+        "site".to_owned(),
+    ] {
+        assert_eq!(scheme.strip(&topic), None, "{}", topic);
+        assert_eq!(scheme.uuid(&topic), None, "{}", topic);
+        assert_eq!(scheme.parse_cmd_topic(&topic), None, "{}", topic);
+    }
+    assert_eq!(scheme.parse_cmd_topic("site2/all/command/board_led"), None);
+    assert_eq!(
+        scheme.parse_cmd_topic("site/all/command/board_led"),
+        Some((Target::All, CommandKind::BoardLed))
+    );
+}
@@ -0,0 +1,151 @@
+#![cfg(feature = "sealing")]
+
+mod common;
+
+use common::{assert_too_short, enveloped, UUID};
+use mqtt_messages::envelope::{MessageType, HEADER_LEN};
+use mqtt_messages::sealing::{
+    device_key, Key, Opener, Party, Sealer, NONCE_LEN, SEALED_OVERHEAD, TAG_LEN,
+};
+use mqtt_messages::ConvertError;
+
+const SECRET: &[u8] = b"correct horse battery staple";
+const TOPIC: &str = "6188eec9-6d3a-4eac-996f-ac4ab13f312d/sensor_data/temperature";
+const PAYLOAD: &[u8] = &[10, 20, 30, 40];
+
+fn key() -> Key {
+    device_key(SECRET, UUID)
+}
+
+fn seal(sealer: &mut Sealer, payload: &[u8]) -> Vec<u8> {
+    let mut buf = vec![0; SEALED_OVERHEAD + payload.len()];
+    let len = sealer.seal_into(TOPIC, payload, &mut buf).unwrap();
+    assert_eq!(len, buf.len());
+    buf
+}
+
+fn open(data: &[u8]) -> Result<Vec<u8>, ConvertError> {
+    let mut data = data.to_vec();
+    Ok(Opener::new(&key(), Party::Board)
+        .open_in_place(TOPIC, &mut data)?
+        .to_vec())
+}
+
+#[test]
+fn round_trip() {
+    let mut sealer = Sealer::new(&key(), Party::Board, 7);
+    for payload in [PAYLOAD, &[]] {
+        let data = seal(&mut sealer, payload);
+        assert_eq!(data[1], MessageType::Sealed as u8);
+        assert_eq!(open(&data).unwrap(), payload);
+    }
+    let data = seal(&mut sealer, PAYLOAD);
+    assert_ne!(&data[HEADER_LEN + NONCE_LEN..][..PAYLOAD.len()], PAYLOAD);
+}
+
+#[test]
+fn nonces_are_unique() {
+    let nonce = |data: &[u8]| data[HEADER_LEN..HEADER_LEN + NONCE_LEN].to_vec();
+    let mut sealer = Sealer::new(&key(), Party::Board, 7);
+    let first = seal(&mut sealer, PAYLOAD);
+    let second = seal(&mut sealer, PAYLOAD);
+    assert_ne!(nonce(&first), nonce(&second));
+
+    let mut other_session = Sealer::new(&key(), Party::Board, 8);
+    assert_ne!(nonce(&seal(&mut other_session, PAYLOAD)), nonce(&first));
+    let mut other_party = Sealer::new(&key(), Party::Host, 7);
+    assert_ne!(nonce(&seal(&mut other_party, PAYLOAD)), nonce(&first));
+}
+
+#[test]
+fn nonce_layout() {
+    let mut sealer = Sealer::new(&key(), Party::Host, 0x0102_0304);
+    seal(&mut sealer, PAYLOAD);
+    let data = seal(&mut sealer, PAYLOAD);
+    assert_eq!(
+        data[HEADER_LEN..HEADER_LEN + NONCE_LEN],
+        [1, 1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 1]
+    );
+}
+
+#[test]
+fn tampered() {
+    let data = seal(&mut Sealer::new(&key(), Party::Board, 7), PAYLOAD);
+    for i in HEADER_LEN..data.len() {
+        let mut tampered = data.clone();
+        tampered[i] ^= 0x01;
+        assert!(
+            matches!(open(&tampered), Err(ConvertError::InvalidSeal)),
+            "byte {} flipped",
+            i
+        );
+    }
+}
+
+#[test]
+fn wrong_topic_or_key() {
+    let data = seal(&mut Sealer::new(&key(), Party::Board, 7), PAYLOAD);
+    let other_topic = "6188eec9-6d3a-4eac-996f-ac4ab13f312d/sensor_data/humidity";
+    assert!(matches!(
+        Opener::new(&key(), Party::Board).open_in_place(other_topic, &mut data.clone()),
+        Err(ConvertError::InvalidSeal)
+    ));
+
+    let other = device_key(SECRET, "00000000-0000-0000-0000-000000000000");
+    assert_ne!(other, key());
+    assert!(matches!(
+        Opener::new(&other, Party::Board).open_in_place(TOPIC, &mut data.clone()),
+        Err(ConvertError::InvalidSeal)
+    ));
+}
+
+#[test]
+fn wrong_party() {
+    // a host must not accept its own payloads reflected back at it
+    let data = seal(&mut Sealer::new(&key(), Party::Host, 7), PAYLOAD);
+    assert!(matches!(open(&data), Err(ConvertError::InvalidSeal)));
+}
+
+#[test]
+fn without_nonce_and_tag() {
+    let data = seal(&mut Sealer::new(&key(), Party::Board, 7), &[]);
+    assert_too_short(MessageType::Sealed, &data[HEADER_LEN..], open);
+}
+
+#[test]
+fn not_sealed() {
+    assert!(matches!(
+        open(&enveloped(MessageType::Signed, &[0; NONCE_LEN + TAG_LEN])),
+        Err(ConvertError::UnexpectedMessageType(MessageType::Signed))
+    ));
+}
+
+#[test]
+fn buffer_too_small() {
+    let mut sealer = Sealer::new(&key(), Party::Board, 7);
+    let mut buf = [0; SEALED_OVERHEAD + 3];
+    assert!(matches!(
+        sealer.seal_into(TOPIC, PAYLOAD, &mut buf),
+        Err(ConvertError::BufferTooSmall(len)) if len == SEALED_OVERHEAD + PAYLOAD.len()
+    ));
+    assert_eq!(buf, [0; SEALED_OVERHEAD + 3], "nothing is written");
+}
+
+#[test]
+fn device_keys_differ() {
+    assert_eq!(device_key(SECRET, UUID), key());
+    assert_ne!(device_key(b"another secret", UUID), key());
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn vec_round_trip() {
+    let data = Sealer::new(&key(), Party::Host, 7)
+        .seal(TOPIC, PAYLOAD)
+        .unwrap();
+    assert_eq!(data.len(), SEALED_OVERHEAD + PAYLOAD.len());
+    assert_eq!(
+        Opener::new(&key(), Party::Host).open(TOPIC, &data).unwrap(),
+        PAYLOAD
+    );
+}
@@ -0,0 +1,156 @@
+#![cfg(feature = "signing")]
+
+mod common;
+
+use common::assert_too_short;
+use mqtt_messages::envelope::{MessageType, HEADER_LEN};
+use mqtt_messages::signing::{Signer, Verifier, NONCE_LEN, SIGNED_OVERHEAD, TAG_LEN};
+use mqtt_messages::ConvertError;
+
+const KEY: &[u8] = b"correct horse battery staple";
+const TOPIC: &str = "6188eec9-6d3a-4eac-996f-ac4ab13f312d/command/board_led";
+const PAYLOAD: &[u8] = &[10, 20, 30];
+
+fn sign(signer: &mut Signer, payload: &[u8]) -> Vec<u8> {
+    let mut buf = vec![0; SIGNED_OVERHEAD + payload.len()];
+    let len = signer.sign_into(TOPIC, payload, &mut buf).unwrap();
+    assert_eq!(len, buf.len());
+    buf
+}
+
+#[test]
+fn round_trip() {
+    let mut signer = Signer::new(KEY, 1_000);
+    let data = sign(&mut signer, PAYLOAD);
+    assert_eq!(data[1], MessageType::Signed as u8);
+    assert_eq!(
+        data[HEADER_LEN..HEADER_LEN + NONCE_LEN],
+        1_000u64.to_be_bytes()
+    );
+
+    let mut verifier = Verifier::new(KEY);
+    let signed = verifier.verify(TOPIC, &data).unwrap();
+    assert_eq!(signed.nonce, 1_000);
+    assert_eq!(signed.payload, PAYLOAD);
+    assert_eq!(verifier.last_nonce(), Some(1_000));
+}
+
+#[test]
+fn nonces_increase() {
+    let mut signer = Signer::new(KEY, 7);
+    let mut verifier = Verifier::new(KEY);
+    for nonce in 7..10 {
+        let data = sign(&mut signer, PAYLOAD);
+        assert_eq!(verifier.verify(TOPIC, &data).unwrap().nonce, nonce);
+    }
+}
+
+#[test]
+fn tampered() {
+    let data = sign(&mut Signer::new(KEY, 1), PAYLOAD);
+    // nonce, tag and payload are all covered
+    for i in HEADER_LEN..data.len() {
+        let mut data = data.clone();
+        data[i] ^= 1;
+        assert!(
+            matches!(
+                Verifier::new(KEY).verify(TOPIC, &data),
+                Err(ConvertError::InvalidSignature)
+            ),
+            "byte {}",
+            i
+        );
+    }
+}
+
+#[test]
+fn wrong_topic_or_key() {
+    let data = sign(&mut Signer::new(KEY, 1), PAYLOAD);
+    let other_topic = "6188eec9-6d3a-4eac-996f-ac4ab13f312d/command/other";
+    assert!(matches!(
+        Verifier::new(KEY).verify(other_topic, &data),
+        Err(ConvertError::InvalidSignature)
+    ));
+    assert!(matches!(
+        Verifier::new(b"another key").verify(TOPIC, &data),
+        Err(ConvertError::InvalidSignature)
+    ));
+}
+
+#[test]
+fn replayed_nonce() {
+    let data = sign(&mut Signer::new(KEY, 5), PAYLOAD);
+    let mut verifier = Verifier::new(KEY);
+    verifier.verify(TOPIC, &data).unwrap();
+    assert!(matches!(
+        verifier.verify(TOPIC, &data),
+        Err(ConvertError::StaleNonce(5))
+    ));
+}
+
+#[test]
+fn stale_nonce() {
+    let older = sign(&mut Signer::new(KEY, 4), PAYLOAD);
+    let newer = sign(&mut Signer::new(KEY, 5), PAYLOAD);
+    let mut verifier = Verifier::new(KEY);
+    verifier.verify(TOPIC, &newer).unwrap();
+    assert!(matches!(
+        verifier.verify(TOPIC, &older),
+        Err(ConvertError::StaleNonce(4))
+    ));
+    assert_eq!(verifier.last_nonce(), Some(5), "rejects do not count");
+}
+
+#[test]
+fn stale_after_restart() {
+    let data = sign(&mut Signer::new(KEY, 5), PAYLOAD);
+    let mut verifier = Verifier::with_last_nonce(KEY, Some(5));
+    assert!(matches!(
+        verifier.verify(TOPIC, &data),
+        Err(ConvertError::StaleNonce(5))
+    ));
+
+    let data = sign(&mut Signer::new(KEY, 6), PAYLOAD);
+    assert_eq!(verifier.verify(TOPIC, &data).unwrap().nonce, 6);
+}
+
+#[test]
+fn forged_signature_does_not_advance_the_nonce() {
+    let forged = sign(&mut Signer::new(b"another key", 1_000_000), PAYLOAD);
+    let mut verifier = Verifier::new(KEY);
+    assert!(verifier.verify(TOPIC, &forged).is_err());
+    assert_eq!(verifier.last_nonce(), None);
+
+    let data = sign(&mut Signer::new(KEY, 1), PAYLOAD);
+    assert_eq!(verifier.verify(TOPIC, &data).unwrap().nonce, 1);
+}
+
+#[test]
+fn without_whole_tag() {
+    let data = sign(&mut Signer::new(KEY, 1), &[]);
+    assert_eq!(data.len(), HEADER_LEN + NONCE_LEN + TAG_LEN);
+    assert_too_short(MessageType::Signed, &data[HEADER_LEN..], |data| {
+        Verifier::new(KEY)
+            .verify(TOPIC, data)
+            .map(|signed| signed.nonce)
+    });
+}
+
+#[test]
+fn not_signed() {
+    let mut data = sign(&mut Signer::new(KEY, 1), PAYLOAD);
+    data[1] = MessageType::Command as u8;
+    assert!(matches!(
+        Verifier::new(KEY).verify(TOPIC, &data),
+        Err(ConvertError::UnexpectedMessageType(MessageType::Command))
+    ));
+}
+
+#[test]
+fn buffer_too_small() {
+    let mut buf = [0; SIGNED_OVERHEAD + 2];
+    assert!(matches!(
+        Signer::new(KEY, 1).sign_into(TOPIC, PAYLOAD, &mut buf),
+        Err(ConvertError::BufferTooSmall(_))
+    ));
+}
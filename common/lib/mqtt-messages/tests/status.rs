@@ -0,0 +1,160 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{assert_too_short, enveloped, UUID};
+use mqtt_messages::envelope::{MessageType, HEADER_LEN, MAGIC, VERSION};
+use mqtt_messages::{status_topic, Codec, ConvertError, Heartbeat, ResetReason, Status};
+
+fn heartbeat() -> Heartbeat {
+    Heartbeat {
+        uptime_s: 42,
+        firmware_version: "0.2.0".to_owned(),
+        rssi: Some(-67),
+        free_heap: 123_456,
+        reset_reason: ResetReason::Brownout,
+    }
+}
+
+fn statuses() -> [Status; 3] {
+    [
+        Status::Offline,
+        Status::Online(heartbeat()),
+        Status::Online(Heartbeat {
+            uptime_s: u32::MAX,
+            firmware_version: String::new(),
+            rssi: None,
+            free_heap: 0,
+            reset_reason: ResetReason::Unknown,
+        }),
+    ]
+}
+
+/// `heartbeat()` as `Status::Online`, with another reset reason
+fn online_body(reset_reason: u8) -> Vec<u8> {
+    let uptime = [0, 0, 0, 42];
+    let free_heap = [0, 1, 0xE2, 0x40];
+    let rssi = (-67i8) as u8;
+    [
+        &[1][..],
+        &uptime,
+        &free_heap,
+        &[rssi, reset_reason],
+        b"0.2.0",
+    ]
+    .concat()
+}
+
+#[test]
+fn round_trip() {
+    for status in statuses() {
+        let data = status.encode_with(Codec::Binary).unwrap();
+        assert_eq!(Status::decode_with(Codec::Binary, &data).unwrap(), status);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    for status in statuses() {
+        let data = status.encode_with(Codec::Json).unwrap();
+        assert_eq!(Status::decode_with(Codec::Json, &data).unwrap(), status);
+    }
+    assert_eq!(
+        Status::Offline.encode_with(Codec::Json).unwrap(),
+        br#"{"state":"offline"}"#
+    );
+    let data = Status::Online(heartbeat())
+        .encode_with(Codec::Json)
+        .unwrap();
+    assert!(data.starts_with(br#"{"state":"online","uptime_s":42,"#));
+}
+
+#[test]
+fn layout() {
+    let data = Status::Online(heartbeat())
+        .encode_with(Codec::Binary)
+        .unwrap();
+    let header = [MAGIC | VERSION, MessageType::Status as u8, 0, 16];
+    assert_eq!(data[..HEADER_LEN], header);
+    assert_eq!(data[HEADER_LEN..], online_body(ResetReason::Brownout as u8));
+    assert_eq!(
+        Status::Offline.encode_with(Codec::Binary).unwrap()[HEADER_LEN..],
+        [0]
+    );
+}
+
+#[test]
+fn wrong_length() {
+    let decode = |data: &[u8]| Status::decode_with(Codec::Binary, data);
+    // a heartbeat without the reset reason
+    assert_too_short(MessageType::Status, &online_body(0)[..11], decode);
+    assert!(matches!(
+        decode(&enveloped(MessageType::Status, &[0, 0])),
+        Err(ConvertError::Length(2))
+    ));
+}
+
+#[test]
+fn invalid_state() {
+    for body in [&[][..], &[2], &[0xFF]] {
+        assert!(matches!(
+            Status::decode_with(Codec::Binary, &enveloped(MessageType::Status, body)),
+            Err(ConvertError::InvalidData)
+        ));
+    }
+}
+
+#[test]
+fn unknown_reset_reason() {
+    let data = enveloped(MessageType::Status, &online_body(0xFF));
+    assert_eq!(
+        Status::decode_with(Codec::Binary, &data).unwrap(),
+        Status::Online(Heartbeat {
+            reset_reason: ResetReason::Unknown,
+            ..heartbeat()
+        })
+    );
+    for code in 0..=u8::MAX {
+        let reason = ResetReason::from(code as u32);
+        assert!(reason as u8 == code || reason == ResetReason::Unknown);
+    }
+}
+
+#[test]
+fn invalid_version() {
+    let body = [&online_body(0)[..], &[0xFF, 0xFE]].concat();
+    assert!(matches!(
+        Status::decode_with(Codec::Binary, &enveloped(MessageType::Status, &body)),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_invalid() {
+    for data in [
+        &br#"{"state":"asleep"}"#[..],
+        br#"{"state":"online"}"#,
+        br#"{"state":"online","uptime_s":42,"firmware_version":"0.2.0","rssi":-67,"free_heap":1,"reset_reason":"meteor"}"#,
+        br#"{"state":"online","uptime_s":42,"firmware_version":"0.2.0","rssi":-200,"free_heap":1,"reset_reason":"panic"}"#,
+    ] {
+        assert!(
+            matches!(
+                Status::decode_with(Codec::Json, data),
+                Err(ConvertError::Json(_))
+            ),
+            "{}",
+            String::from_utf8_lossy(data)
+        );
+    }
+}
+
+#[test]
+fn topic() {
+    assert_eq!(
+        status_topic(UUID),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/status"
+    );
+    assert_eq!(Status::Offline.topic(UUID), status_topic(UUID));
+}
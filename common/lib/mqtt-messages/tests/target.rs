@@ -0,0 +1,184 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{enveloped, UUID};
+use mqtt_messages::envelope::MessageType;
+use mqtt_messages::target::{group_name, is_group_name};
+use mqtt_messages::{Codec, Command, CommandKind, ConvertError, Target, TopicScheme};
+
+fn commands() -> [Command; 3] {
+    [
+        Command::JoinGroup(group_name("kitchen").unwrap()),
+        Command::LeaveGroup(group_name("floor-2_east").unwrap()),
+        Command::JoinGroup(group_name(&"g".repeat(32)).unwrap()),
+    ]
+}
+
+#[test]
+fn round_trip() {
+    for command in commands() {
+        let data = command.encode_with(Codec::Binary);
+        assert_eq!(
+            Command::decode_kind_with(Codec::Binary, command.kind(), &data).unwrap(),
+            command
+        );
+    }
+    // the name is the whole payload, with or without an envelope
+    let data = enveloped(MessageType::Command, b"kitchen");
+    assert_eq!(
+        Command::decode_kind(CommandKind::JoinGroup, &data).unwrap(),
+        commands()[0]
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    for command in commands() {
+        let data = command.encode_with(Codec::Json);
+        assert_eq!(
+            Command::decode_kind_with(Codec::Json, command.kind(), &data).unwrap(),
+            command
+        );
+    }
+    assert_eq!(
+        commands()[0].encode_with(Codec::Json),
+        br#"{"join_group":"kitchen"}"#
+    );
+}
+
+#[test]
+fn invalid_names() {
+    let too_long = "g".repeat(33);
+    for name in [
+        &b""[..],
+        too_long.as_bytes(),
+        b"bad name",
+        b"a/b",
+        b"+",
+        b"#",
+        b"caf\xC3\xA9",
+        b"\xFF",
+    ] {
+        assert!(
+            matches!(
+                Command::decode_kind(CommandKind::JoinGroup, name),
+                Err(ConvertError::InvalidData)
+            ),
+            "{}",
+            String::from_utf8_lossy(name)
+        );
+    }
+    assert!(matches!(
+        group_name(&too_long),
+        Err(ConvertError::InvalidData)
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_invalid_names() {
+    use mqtt_messages::CommandRequest;
+
+    for (kind, data) in [
+        (CommandKind::JoinGroup, &br#"{"join_group":"bad name"}"#[..]),
+        (CommandKind::JoinGroup, br#"{"join_group":""}"#),
+        (CommandKind::LeaveGroup, br#"{"leave_group":"a/b"}"#),
+    ] {
+        assert!(
+            matches!(
+                Command::decode_kind_with(Codec::Json, kind, data),
+                Err(ConvertError::InvalidData)
+            ),
+            "{}",
+            String::from_utf8_lossy(data)
+        );
+        assert!(matches!(
+            CommandRequest::decode_kind_with(Codec::Json, kind, data),
+            Err(ConvertError::InvalidData)
+        ));
+    }
+    assert!(matches!(
+        CommandRequest::decode_kind_with(
+            Codec::Json,
+            CommandKind::JoinGroup,
+            br#"{"id":1,"command":{"join_group":"bad name"}}"#
+        ),
+        Err(ConvertError::InvalidData)
+    ));
+    // too long for the name buffer already
+    let data = format!(r#"{{"join_group":"{}"}}"#, "g".repeat(33));
+    assert!(matches!(
+        Command::decode_kind_with(Codec::Json, CommandKind::JoinGroup, data.as_bytes()),
+        Err(ConvertError::Json(_))
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_wrong_kind() {
+    assert!(matches!(
+        Command::decode_kind_with(
+            Codec::Json,
+            CommandKind::JoinGroup,
+            br#"{"leave_group":"kitchen"}"#
+        ),
+        Err(ConvertError::InvalidPath)
+    ));
+}
+
+#[test]
+fn group_names() {
+    assert!(is_group_name("kitchen"));
+    assert!(is_group_name("A-1_b"));
+    assert!(!is_group_name(""));
+    assert!(!is_group_name("all/command"));
+    assert!(!is_group_name("with space"));
+}
+
+#[test]
+fn topics() {
+    let command = &commands()[0];
+    let scheme = TopicScheme::BARE;
+    assert_eq!(
+        command.topic_to(&scheme, Target::Device(UUID)),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/command/join_group"
+    );
+    assert_eq!(
+        command.topic_to(&scheme, Target::All),
+        "all/command/join_group"
+    );
+    assert_eq!(
+        command.topic_to(&scheme, Target::Group("kitchen")),
+        "group/kitchen/command/join_group"
+    );
+
+    let scheme = TopicScheme::new("org/site").unwrap();
+    assert_eq!(
+        command.topic_to(&scheme, Target::Group("kitchen")),
+        "org/site/group/kitchen/command/join_group"
+    );
+    assert_eq!(scheme.cmd_filter(Target::All), "org/site/all/command/+");
+}
+
+#[test]
+fn parse_topics() {
+    for scheme in [TopicScheme::BARE, TopicScheme::new("org/site").unwrap()] {
+        for target in [Target::Device(UUID), Target::All, Target::Group("kitchen")] {
+            for kind in CommandKind::ALL {
+                let topic = format!("{}{}", scheme.cmd_topic_fragment(target), kind.name());
+                assert_eq!(scheme.parse_cmd_topic(&topic), Some((target, kind)));
+            }
+        }
+    }
+    for topic in [
+        "all/command/reboot",
+        "group/bad name/command/board_led",
+        "group/command/board_led",
+        "all/status",
+        "command/board_led",
+    ] {
+        assert_eq!(TopicScheme::BARE.parse_cmd_topic(topic), None, "{}", topic);
+    }
+}
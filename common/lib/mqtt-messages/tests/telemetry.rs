@@ -0,0 +1,175 @@
+#![cfg(feature = "std")]
+
+mod common;
+
+use common::{assert_exact_len, enveloped, UUID};
+use mqtt_messages::envelope::{MessageType, HEADER_LEN};
+use mqtt_messages::{Codec, ConvertError, Telemetry, TelemetryKind, Vector3, MAX_TELEMETRY_LEN};
+
+fn readings() -> [Telemetry; 6] {
+    [
+        Telemetry::ChipTemperature(31.5),
+        Telemetry::AmbientTemperature(-4.25),
+        Telemetry::Humidity(45.2),
+        Telemetry::Accel(Vector3::new(0.0, -1.0, 0.5)),
+        Telemetry::Gyro(Vector3::new(120.0, 0.25, -3.5)),
+        Telemetry::StepCount(u32::MAX),
+    ]
+}
+
+#[test]
+fn round_trip() {
+    for telemetry in readings() {
+        let data = telemetry.encode();
+        assert_eq!(data.len(), telemetry.kind().payload_len());
+        assert_eq!(
+            Telemetry::decode(telemetry.kind(), &data).unwrap(),
+            telemetry
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_round_trip() {
+    for telemetry in readings() {
+        let data = telemetry.encode_with(Codec::Json);
+        assert_eq!(
+            Telemetry::decode_with(Codec::Json, telemetry.kind(), &data).unwrap(),
+            telemetry
+        );
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_format() {
+    assert_eq!(
+        Telemetry::StepCount(42).encode_with(Codec::Json),
+        br#"{"kind":"step_count","value":42}"#
+    );
+    assert_eq!(
+        Telemetry::decode_with(
+            Codec::Json,
+            TelemetryKind::Accel,
+            br#"{"kind":"accel","value":{"x":1.0,"y":2.0,"z":3.0}}"#
+        )
+        .unwrap(),
+        Telemetry::Accel(Vector3::new(1.0, 2.0, 3.0))
+    );
+}
+
+#[test]
+fn binary_codec() {
+    let telemetry = Telemetry::Humidity(45.2);
+    assert_eq!(telemetry.encode_with(Codec::Binary), telemetry.encode());
+    assert_eq!(Codec::from_name("binary"), Some(Codec::Binary));
+    assert_eq!(Codec::from_name("yaml"), None);
+}
+
+#[test]
+fn big_endian() {
+    assert_eq!(Telemetry::StepCount(0x0102_0304).encode(), [1, 2, 3, 4]);
+    assert_eq!(
+        Telemetry::Accel(Vector3::new(1.0, 2.0, 3.0)).encode()[4..8],
+        2.0f32.to_be_bytes()
+    );
+}
+
+#[test]
+fn enveloped_round_trip() {
+    for telemetry in readings() {
+        let data = telemetry.enveloped();
+        assert_eq!(
+            data,
+            enveloped(telemetry.kind().into(), &telemetry.encode())
+        );
+        assert_eq!(
+            Telemetry::decode(telemetry.kind(), &data).unwrap(),
+            telemetry
+        );
+
+        let mut buf = [0; HEADER_LEN + MAX_TELEMETRY_LEN];
+        let len = telemetry.enveloped_into(&mut buf).unwrap();
+        assert_eq!(buf[..len], data);
+    }
+}
+
+#[test]
+fn wrong_length() {
+    for telemetry in readings() {
+        let kind = telemetry.kind();
+        assert_exact_len(kind.into(), &telemetry.encode(), |data| {
+            Telemetry::decode(kind, data)
+        });
+
+        // without the legacy length, a bare payload has to be an envelope
+        let mut data = telemetry.encode();
+        data.push(0);
+        assert!(matches!(
+            Telemetry::decode(kind, &data),
+            Err(ConvertError::InvalidHeader)
+        ));
+    }
+}
+
+#[test]
+fn wrong_kind() {
+    let data = Telemetry::ChipTemperature(31.5).enveloped();
+    assert!(matches!(
+        Telemetry::decode(TelemetryKind::Humidity, &data),
+        Err(ConvertError::UnexpectedMessageType(
+            MessageType::ChipTemperature
+        ))
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json_invalid() {
+    for data in [
+        &br#"{"kind":"pressure","value":1013.0}"#[..],
+        br#"{"kind":"humidity","value":"wet"}"#,
+        br#"{"kind":"humidity","value":45"#,
+    ] {
+        assert!(matches!(
+            Telemetry::decode_with(Codec::Json, TelemetryKind::Humidity, data),
+            Err(ConvertError::Json(_))
+        ));
+    }
+    assert!(matches!(
+        Telemetry::decode_with(
+            Codec::Json,
+            TelemetryKind::Humidity,
+            br#"{"kind":"step_count","value":1}"#
+        ),
+        Err(ConvertError::UnexpectedMessageType(MessageType::StepCount))
+    ));
+}
+
+#[test]
+fn buffer_too_small() {
+    let mut buf = [0; 11];
+    assert!(matches!(
+        Telemetry::Gyro(Vector3::default()).encode_into(&mut buf),
+        Err(ConvertError::BufferTooSmall(12))
+    ));
+}
+
+#[test]
+fn topics() {
+    for kind in TelemetryKind::ALL {
+        assert_eq!(
+            TelemetryKind::from_topic(UUID, &kind.topic(UUID)),
+            Some(kind)
+        );
+    }
+    assert_eq!(
+        TelemetryKind::Humidity.topic(UUID),
+        "6188eec9-6d3a-4eac-996f-ac4ab13f312d/sensor_data/humidity"
+    );
+    assert_eq!(
+        TelemetryKind::from_topic(UUID, &format!("{}/sensor_data/pressure", UUID)),
+        None
+    );
+}
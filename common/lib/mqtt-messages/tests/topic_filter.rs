@@ -0,0 +1,144 @@
+use mqtt_messages::topic_filter::{TopicFilter, TopicFilterError};
+
+const UUID: &str = "6188eec9-6d3a-4eac-996f-ac4ab13f312d";
+
+#[test]
+fn valid_filters() {
+    for filter in [
+        "#",
+        "+",
+        "/",
+        "sport/#",
+        "sport/tennis/#",
+        "sport/+",
+        "sport/+/player1",
+        "+/+",
+        "/+",
+        "+/tennis/#",
+        "sport//player1",
+        "$SYS/#",
+        "+/sensor_data/+",
+    ] {
+        assert!(
+            TopicFilter::new(filter).is_ok(),
+            "{:?} should be valid",
+            filter
+        );
+    }
+}
+
+#[test]
+fn invalid_filters() {
+    let table = [
+        ("", TopicFilterError::Empty),
+        ("sport/tennis#", TopicFilterError::InvalidMultiLevelWildcard),
+        (
+            "sport/tennis/#/ranking",
+            TopicFilterError::InvalidMultiLevelWildcard,
+        ),
+        ("#/", TopicFilterError::InvalidMultiLevelWildcard),
+        ("##", TopicFilterError::InvalidMultiLevelWildcard),
+        ("sport+", TopicFilterError::InvalidSingleLevelWildcard),
+        (
+            "sport/+tennis",
+            TopicFilterError::InvalidSingleLevelWildcard,
+        ),
+        ("++", TopicFilterError::InvalidSingleLevelWildcard),
+        ("sport/\0", TopicFilterError::NullCharacter),
+    ];
+    for (filter, expected) in table {
+        assert_eq!(TopicFilter::new(filter), Err(expected), "{:?}", filter);
+    }
+
+    let long = "a".repeat(65_536);
+    assert_eq!(
+        TopicFilter::new(&long),
+        Err(TopicFilterError::TooLong(65_536))
+    );
+}
+
+#[test]
+fn matching() {
+    let table = [
+        // multi-level wildcard
+        ("sport/tennis/player1/#", "sport/tennis/player1", true),
+        (
+            "sport/tennis/player1/#",
+            "sport/tennis/player1/ranking",
+            true,
+        ),
+        (
+            "sport/tennis/player1/#",
+            "sport/tennis/player1/score/wimbledon",
+            true,
+        ),
+        ("sport/tennis/player1/#", "sport/tennis/player2", false),
+        ("sport/#", "sport", true),
+        ("#", "sport/tennis/player1", true),
+        ("#", "/", true),
+        // single-level wildcard
+        ("sport/tennis/+", "sport/tennis/player1", true),
+        ("sport/tennis/+", "sport/tennis/player2", true),
+        ("sport/tennis/+", "sport/tennis/player1/ranking", false),
+        ("sport/+", "sport", false),
+        ("sport/+", "sport/", true),
+        ("+/+", "/finance", true),
+        ("/+", "/finance", true),
+        ("+", "/finance", false),
+        ("+/tennis/#", "sport/tennis/player1", true),
+        ("+/tennis/#", "sport/football/player1", false),
+        // no wildcards
+        ("sport/tennis", "sport/tennis", true),
+        ("sport/tennis", "sport/Tennis", false),
+        ("sport/tennis", "sport/tennis/", false),
+        ("sport//tennis", "sport//tennis", true),
+        // `$` topics
+        ("#", "$SYS/broker/uptime", false),
+        ("+/monitor/Clients", "$SYS/monitor/Clients", false),
+        ("$SYS/#", "$SYS/broker/uptime", true),
+        ("$SYS/monitor/+", "$SYS/monitor/Clients", true),
+        // topic names must not contain wildcards or be empty
+        ("#", "sport/+", false),
+        ("#", "sport/#", false),
+        ("#", "", false),
+        // this crate's topics
+        (
+            "+/sensor_data/+",
+            "6188eec9-6d3a-4eac-996f-ac4ab13f312d/sensor_data/humidity",
+            true,
+        ),
+        (
+            "+/sensor_data/+",
+            "6188eec9-6d3a-4eac-996f-ac4ab13f312d/hello",
+            false,
+        ),
+        (
+            "+/hello",
+            "6188eec9-6d3a-4eac-996f-ac4ab13f312d/hello",
+            true,
+        ),
+    ];
+    for (filter, topic, expected) in table {
+        let matches = TopicFilter::new(filter).unwrap().matches(topic);
+        assert_eq!(matches, expected, "{:?} against {:?}", filter, topic);
+    }
+}
+
+#[test]
+fn wildcard_levels() {
+    let filter = TopicFilter::new("+/sensor_data/+").unwrap();
+    let topic = format!("{}/sensor_data/humidity", UUID);
+    assert_eq!(filter.uuid(&topic), Some(UUID));
+    assert_eq!(filter.wildcard(&topic, 1), Some("humidity"));
+    assert_eq!(filter.wildcard(&topic, 2), None);
+
+    let hello = format!("{}/hello", UUID);
+    assert_eq!(filter.uuid(&hello), None);
+
+    let filter = TopicFilter::new("+/command/#").unwrap();
+    let topic = format!("{}/command/board_led", UUID);
+    assert_eq!(filter.uuid(&topic), Some(UUID));
+
+    let filter = TopicFilter::new("sport/#").unwrap();
+    assert_eq!(filter.uuid("sport/tennis"), None);
+}
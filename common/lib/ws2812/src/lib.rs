@@ -0,0 +1,63 @@
+//! Encodes bytes for WS2812 LEDs as RMT items
+//!
+//! Plain `no_std` Rust, so it is tested on the host. The board support crate
+//! hands `encode` to the RMT driver as its translator.
+
+#![no_std]
+
+pub const T0H_NS: u32 = 350;
+pub const T0L_NS: u32 = 1000;
+pub const T1H_NS: u32 = 1000;
+pub const T1L_NS: u32 = 350;
+
+/// Items a byte becomes, one per bit
+pub const ITEMS_PER_BYTE: usize = 8;
+
+/// The widest duration an RMT item holds, in ticks
+pub const MAX_TICKS: u32 = 0x7fff;
+
+/// How long an RMT channel keeps the line high and low for a 0 and a 1 bit
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timing {
+    /// `rmt_item32_t` of a 0 bit
+    pub bit0: u32,
+    /// `rmt_item32_t` of a 1 bit
+    pub bit1: u32,
+}
+
+impl Timing {
+    /// The WS2812 datasheet timing on a channel counting at `clock_hz`
+    pub fn new(clock_hz: u32) -> Self {
+        let ticks = |ns: u32| (clock_hz as u64 * ns as u64 / 1_000_000_000) as u32;
+        Self {
+            bit0: item(ticks(T0H_NS), ticks(T0L_NS)),
+            bit1: item(ticks(T1H_NS), ticks(T1L_NS)),
+        }
+    }
+}
+
+/// An RMT item, `high` ticks high followed by `low` ticks low
+///
+/// Durations are cut to `MAX_TICKS`.
+pub fn item(high: u32, low: u32) -> u32 {
+    high.min(MAX_TICKS) | 1 << 15 | low.min(MAX_TICKS) << 16
+}
+
+/// Encodes `src` into `dest`, most significant bit first
+///
+/// Only whole bytes are encoded. Returns how many bytes of `src` went into
+/// how many items of `dest`.
+pub fn encode(timing: &Timing, src: &[u8], dest: &mut [u32]) -> (usize, usize) {
+    let mut size = 0;
+    for (byte, items) in src.iter().zip(dest.chunks_exact_mut(ITEMS_PER_BYTE)) {
+        for (i, item) in items.iter_mut().enumerate() {
+            *item = if byte & (0x80 >> i) != 0 {
+                timing.bit1
+            } else {
+                timing.bit0
+            };
+        }
+        size += 1;
+    }
+    (size, size * ITEMS_PER_BYTE)
+}
@@ -0,0 +1,62 @@
+use ws2812::{encode, item, Timing, ITEMS_PER_BYTE, MAX_TICKS};
+
+/// 40 MHz, what the board's RMT counts at with `clk_div: 2`
+const CLOCK_HZ: u32 = 40_000_000;
+
+#[test]
+fn item_layout() {
+    // duration0 in bits 0..15, level0 in 15, duration1 in 16..31, level1 in 31
+    assert_eq!(item(14, 40), 14 | 1 << 15 | 40 << 16);
+    assert_eq!(item(0, 0), 1 << 15);
+}
+
+#[test]
+fn item_cuts_long_durations() {
+    assert_eq!(item(u32::MAX, u32::MAX), item(MAX_TICKS, MAX_TICKS));
+    assert_eq!(item(u32::MAX, 0) >> 31, 0, "low level stays low");
+}
+
+#[test]
+fn datasheet_timing() {
+    let timing = Timing::new(CLOCK_HZ);
+    assert_eq!(timing.bit0, item(14, 40));
+    assert_eq!(timing.bit1, item(40, 14));
+}
+
+#[test]
+fn timing_follows_the_clock() {
+    let timing = Timing::new(CLOCK_HZ / 2);
+    assert_eq!(timing.bit0, item(7, 20));
+    assert_eq!(timing.bit1, item(20, 7));
+}
+
+#[test]
+fn encodes_msb_first() {
+    let timing = Timing::new(CLOCK_HZ);
+    let (zero, one) = (timing.bit0, timing.bit1);
+    let mut dest = [0; 16];
+
+    assert_eq!(encode(&timing, &[0b1010_0001, 0xff], &mut dest), (2, 16));
+    assert_eq!(
+        dest,
+        [one, zero, one, zero, zero, zero, zero, one, one, one, one, one, one, one, one, one]
+    );
+}
+
+#[test]
+fn stops_at_whole_bytes() {
+    let timing = Timing::new(CLOCK_HZ);
+    let mut dest = [0; 2 * ITEMS_PER_BYTE - 1];
+
+    assert_eq!(encode(&timing, &[0, 0, 0], &mut dest), (1, ITEMS_PER_BYTE));
+    assert_eq!(dest[ITEMS_PER_BYTE..], [0; ITEMS_PER_BYTE - 1]);
+}
+
+#[test]
+fn empty_input() {
+    let timing = Timing::new(CLOCK_HZ);
+    let mut dest = [0; ITEMS_PER_BYTE];
+
+    assert_eq!(encode(&timing, &[], &mut dest), (0, 0));
+    assert_eq!(encode(&timing, &[0xff], &mut []), (0, 0));
+}
@@ -2,17 +2,42 @@
 
 from time import sleep
 from random import randint
-from struct import unpack
+from struct import calcsize, unpack, unpack_from
+import json
+import sys
+from uuid import NAMESPACE_URL, UUID, uuid5
 
 import paho.mqtt.client as mqtt
 import toml
 
 uuid = None
 
+# `get_uuid::NAMESPACE`, for UUIDs derived from a `device_name`
+NAMESPACE = uuid5(NAMESPACE_URL, "https://github.com/esp-rs/std-training/get-uuid")
+
 
 def get_uuid():
+    """The UUID of the board to talk to
+
+    Boards derive their UUID from their MAC, they only go by the one in
+    `uuid.toml` with `override = true` there. Otherwise, pass the UUID the
+    board logs at boot as the first argument.
+    """
+    if len(sys.argv) > 1:
+        return sys.argv[1]
     d = toml.load(open("../../common/lib/get-uuid/uuid.toml", "r"))
-    return list(d.values())[0]["uuid"]
+    identity = list(d.values())[0]
+    if not identity.get("override", False):
+        sys.exit(
+            "usage: mqtt.py <board uuid>, or set `override = true` in uuid.toml"
+        )
+    if "uuid" in identity:
+        return identity["uuid"]
+    if identity.get("device_name"):
+        # what get-uuid's build.rs derives from a device name
+        namespace = UUID(identity.get("namespace", str(NAMESPACE)))
+        return str(uuid5(namespace, identity["device_name"]))
+    sys.exit("uuid.toml needs `uuid` or `device_name`")
 
 
 def get_config():
@@ -34,10 +59,113 @@ def on_connect(client, userdata, flags, rc):
     # client.subscribe("$SYS/#")
 
 
+# payload formats of `mqtt_messages::Telemetry`, keyed by the last topic level
+TELEMETRY_FORMATS = {
+    "temperature": ">f",
+    "ambient_temperature": ">f",
+    "humidity": ">f",
+    "accel": ">fff",
+    "gyro": ">fff",
+    "step_count": ">I",
+}
+
+# `mqtt_messages::envelope`: magic/version, type, u16 body length
+MAGIC = 0xE0
+MAGIC_MASK = 0xF0
+VERSION = 1
+HEADER_FORMAT = ">BBH"
+TELEMETRY_BATCH = 0x20
+# telemetry message types and the topic level of their kind
+TELEMETRY_TYPES = {
+    0x10: "temperature",
+    0x11: "ambient_temperature",
+    0x12: "humidity",
+    0x13: "accel",
+    0x14: "gyro",
+    0x15: "step_count",
+}
+
+
+def unpack_envelope(payload):
+    """Returns message type and body of an enveloped payload"""
+    if len(payload) < calcsize(HEADER_FORMAT):
+        raise ValueError(f"{len(payload)} bytes are too short for a header")
+    magic_version, message_type, length = unpack_from(HEADER_FORMAT, payload)
+    if magic_version & MAGIC_MASK != MAGIC:
+        raise ValueError("not an envelope")
+    if magic_version & ~MAGIC_MASK != VERSION:
+        raise ValueError(f"unsupported envelope version {magic_version & ~MAGIC_MASK}")
+    body = payload[calcsize(HEADER_FORMAT):]
+    if length != len(body):
+        raise ValueError(f"header says {length} bytes, body has {len(body)}")
+    return message_type, body
+
+
+def unpack_values(kind, data):
+    fmt = TELEMETRY_FORMATS[kind]
+    if len(data) != calcsize(fmt):
+        raise ValueError(f"{len(data)} bytes for {kind}")
+    return unpack(fmt, data)
+
+
+def unpack_batch(body):
+    """Returns `(kind, timestamp_ms, values)` for each sample of a batch body"""
+    if len(body) < 8:
+        raise ValueError("batch without a base timestamp")
+    (base,) = unpack_from(">Q", body)
+    samples = []
+    offset = 8
+    while offset < len(body):
+        if len(body) - offset < 5:
+            raise ValueError("cut batch record")
+        delta, message_type = unpack_from(">IB", body, offset)
+        offset += 5
+        kind = TELEMETRY_TYPES.get(message_type)
+        if kind is None:
+            raise ValueError(f"message type {message_type:#04x} in a batch")
+        end = offset + calcsize(TELEMETRY_FORMATS[kind])
+        samples.append((kind, base + delta, unpack_values(kind, body[offset:end])))
+        offset = end
+    return samples
+
+
+def unpack_telemetry(kind, payload):
+    """Returns `(kind, timestamp_ms, values)` for the samples in `payload`
+
+    Timestamps are only known for batched samples, the others have `None`.
+    """
+    if kind in TELEMETRY_FORMATS and len(payload) == calcsize(TELEMETRY_FORMATS[kind]):
+        # legacy v0, a bare payload of the topic's kind
+        return [(kind, None, unpack(TELEMETRY_FORMATS[kind], payload))]
+    message_type, body = unpack_envelope(payload)
+    if message_type == TELEMETRY_BATCH:
+        return unpack_batch(body)
+    if message_type not in TELEMETRY_TYPES:
+        raise ValueError(f"message type {message_type:#04x} is not telemetry")
+    kind = TELEMETRY_TYPES[message_type]
+    return [(kind, None, unpack_values(kind, body))]
+
+
+def format_value(value):
+    # counts are integers, the rest are floats
+    return f"{value:d}" if isinstance(value, int) else f"{value:.1f}"
+
+
 def on_message(client, userdata, msg):
-    if "sensor_data" in msg.topic:
-        [mcu_temp] = unpack(">f", msg.payload)
-        print(f"{msg.topic} {mcu_temp:.1f}")
+    kind = msg.topic.rsplit("/", 1)[-1]
+    if msg.payload.startswith(b"{"):
+        # board runs with `payload_codec = "json"`
+        print(f"{msg.topic} {json.loads(msg.payload)}")
+    elif "sensor_data" in msg.topic:
+        try:
+            samples = unpack_telemetry(kind, msg.payload)
+        except ValueError as e:
+            print(f"{msg.topic} invalid payload ({e}): {msg.payload}")
+            return
+        for kind, timestamp_ms, values in samples:
+            at = "" if timestamp_ms is None else f" at {timestamp_ms} ms"
+            values = ", ".join(format_value(v) for v in values)
+            print(f"{msg.topic} {kind}{at} {values}")
     else:
         print(f"{msg.topic} {msg.payload}")
 
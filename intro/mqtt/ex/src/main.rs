@@ -1,5 +1,6 @@
 use bsc::{
     led::{RGB8, WS2812RMT},
+    nvs::Nvs,
     temp_sensor::BoardTempSensor,
     wifi::wifi,
 };
@@ -12,9 +13,16 @@ use embedded_svc::mqtt::client::{
 use esp32_c3_dkc02_bsc as bsc;
 use esp_idf_svc::{
     log::EspLogger,
-    mqtt::client::{EspMqttClient, EspMqttMessage, MqttClientConfiguration},
+    mqtt::client::{EspMqttClient, EspMqttMessage, LwtConfiguration, MqttClientConfiguration},
+};
+use std::{
+    borrow::Cow,
+    convert::TryFrom,
+    sync::mpsc::{self, Receiver, Sender},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
 };
-use std::{borrow::Cow, convert::TryFrom, thread::sleep, time::Duration};
 // If using the `binstart` feature of `esp-idf-sys`, always keep this module imported
 use esp_idf_sys as _;
 use log::{error, info, warn};
@@ -23,14 +31,42 @@ use log::{error, info, warn};
 use mqtt_messages::{
     hello_topic,
     color_topic,
-    temperature_data_topic,
+    Ack,
+    AckStatus,
+    Batcher,
+    Capabilities,
+    Codec,
     Command,
-    RawCommandData,
+    CommandKind,
+    CommandRequest,
     cmd_topic_fragment,
     ColorData,
+    ConfigKey,
+    ConfigValue,
+    ConvertError,
+    GroupName,
+    Heartbeat,
+    ResetReason,
+    Sensor,
+    Settings,
+    Status,
+    Telemetry,
+    TelemetryKind,
+    home_assistant,
+    ota::ota_topic,
+    OtaReport,
+    OtaStart,
+    sealing::{self, Opener, Party, Sealer},
+    signing::Verifier,
+    status_topic,
+    target::{self, Target},
+    TopicScheme,
 };
 
-const UUID: &'static str = get_uuid::uuid();
+mod player;
+mod updater;
+
+use player::Player;
 
 #[toml_cfg::toml_config]
 pub struct Config {
@@ -44,6 +80,74 @@ pub struct Config {
     wifi_ssid: &'static str,
     #[default("")]
     wifi_psk: &'static str,
+    #[default("binary")]
+    payload_codec: &'static str,
+    #[default(false)]
+    home_assistant: bool,
+    #[default(30)]
+    status_interval_s: u64,
+    #[default(1)]
+    batch_max_samples: usize,
+    #[default(60)]
+    batch_max_age_s: u64,
+    #[default("")]
+    command_key: &'static str,
+    #[default("")]
+    sealing_secret: &'static str,
+    #[default("")]
+    groups: &'static str,
+    #[default("")]
+    topic_prefix: &'static str,
+    #[default(1000)]
+    publish_interval_ms: u32,
+    #[default(255)]
+    led_brightness: u32,
+    #[default(1)]
+    mqtt_qos: u32,
+}
+
+/// What the MQTT callback leaves to the main loop, as it can neither publish
+/// nor subscribe itself
+enum Action {
+    Ack(Ack),
+    JoinGroup(GroupName),
+    LeaveGroup(GroupName),
+    /// publish the value, and persist it if it `changed`
+    Config { value: ConfigValue, changed: bool },
+    /// publish how the firmware update goes, restart once it is done
+    Ota(OtaReport),
+}
+
+/// NVS key of the last nonce `CommandVerifier` accepted
+const LAST_NONCE: &str = "last_nonce";
+
+/// A `Verifier` that remembers the last accepted nonce across reboots, so
+/// commands captured before a reboot stay rejected after it
+struct CommandVerifier {
+    verifier: Verifier<'static>,
+    nvs: Nvs,
+}
+
+impl CommandVerifier {
+    fn new(key: &'static [u8]) -> anyhow::Result<Self> {
+        let nvs = Nvs::open("mqtt")?;
+        let last_nonce = nvs.get_u64(LAST_NONCE)?;
+        Ok(Self {
+            verifier: Verifier::with_last_nonce(key, last_nonce),
+            nvs,
+        })
+    }
+
+    /// The original payload of `data`, once its nonce is persisted
+    fn verify<'a>(&mut self, topic: &str, data: &'a [u8]) -> anyhow::Result<&'a [u8]> {
+        let signed = self
+            .verifier
+            .verify(topic, data)
+            .map_err(|e| anyhow::anyhow!("{:?}", e))?;
+        // before acting on it, so a reboot cannot make it valid again
+        self.nvs.set_u64(LAST_NONCE, signed.nonce)?;
+        Ok(signed.payload)
+    }
 }
 
 fn main() -> anyhow::Result<()> {
@@ -51,12 +155,18 @@ fn main() -> anyhow::Result<()> {
     // Setup 
     esp_idf_sys::link_patches();
 
+    let boot = Instant::now();
+    let reset_reason = ResetReason::from(unsafe { esp_idf_sys::esp_reset_reason() } as u32);
+
     EspLogger::initialize_default();
 
     let app_config = CONFIG;
 
+    // the same on every boot, unless `uuid.toml` overrides it
+    let uuid = get_uuid::device_uuid(base_mac()?);
     info!("our UUID is:");
-    info!("{}", UUID);
+    info!("{} (alias {})", uuid, get_uuid::alias(&uuid));
+    let uuid = uuid.to_string();
 
     let mut temp_sensor = BoardTempSensor::new_taking_peripherals();
 
@@ -65,6 +175,15 @@ fn main() -> anyhow::Result<()> {
 
     let _wifi = wifi(app_config.wifi_ssid, app_config.wifi_psk)?;
 
+    // remote changes are kept in flash, `wifi()` initialized it
+    let mut config_nvs = Nvs::open("config")?;
+    let settings = Arc::new(Mutex::new(load_settings(&app_config, &config_nvs)?));
+
+    let codec = Codec::from_name(app_config.payload_codec).unwrap_or_else(|| {
+        warn!("unknown payload codec {:?}, using binary", app_config.payload_codec);
+        Codec::Binary
+    });
+
     // Client configuration:
     let broker_url = if app_config.mqtt_user != "" {
         format!(
@@ -80,16 +199,82 @@ fn main() -> anyhow::Result<()> {
     dbg!(&broker_url);
     dbg!(&broker_url);
     
-    let mqtt_config = MqttClientConfiguration::default();
+    // every topic of ours starts with `root`
+    let scheme = TopicScheme::new(app_config.topic_prefix)
+        .map_err(|_| anyhow::anyhow!("invalid topic_prefix {:?}", app_config.topic_prefix))?;
+    let root = scheme.root(&uuid);
+
+    // with a secret, everything we publish is sealed and everything we receive has to be
+    let (mut sealer, opener) = if app_config.sealing_secret.is_empty() {
+        (None, None)
+    } else {
+        let key = sealing::device_key(app_config.sealing_secret.as_bytes(), &uuid);
+        // a new session on every boot, so nonces never repeat
+        let session = Nvs::open("mqtt")?.increment_u32("boot_count")?;
+        (Some(Sealer::new(&key, Party::Board, session)), Some(Opener::new(&key, Party::Host)))
+    };
+
+    // the broker publishes this (retained) for us if we vanish without disconnecting
+    let status_topic = status_topic(&root);
+    let offline = sealed(&mut sealer, &status_topic, Status::Offline.encode_with(codec))?;
+    let mqtt_config = MqttClientConfiguration {
+        lwt: Some(LwtConfiguration {
+            topic: &status_topic,
+            payload: &offline,
+            qos: QoS::AtLeastOnce,
+            retain: true,
+        }),
+        ..Default::default()
+    };
+
+    // the MQTT callback cannot publish, so acknowledgements go through the main loop
+    let (action_tx, action_rx) = mpsc::channel();
+
+    // with a key, only act on messages signed by a host that knows it
+    let mut verifier = if app_config.command_key.is_empty() {
+        None
+    } else {
+        Some(CommandVerifier::new(app_config.command_key.as_bytes())?)
+    };
 
     // Your Code:
     // 1. Create a client with default configuration and empty handler
     // Part2: Modify to handle color topic subscription
+    // animations keep running between messages
+    let player = Arc::new(Mutex::new(Player::new(led)));
+    let render_player = player.clone();
+    let render_settings = settings.clone();
+    thread::Builder::new()
+        .stack_size(4096)
+        .spawn(move || loop {
+            thread::sleep(player::FRAME);
+            let brightness = render_settings.lock().unwrap().led_brightness;
+            if let Err(e) = render_player.lock().unwrap().render(brightness) {
+                warn!("could not render animation: {:?}", e);
+            }
+        })?;
+
+    let callback_settings = settings.clone();
+    // firmware updates run whatever they download, so they need a signed request
+    let ota_enabled = verifier.is_some();
+    let callback_ota_topic = ota_enabled.then(|| ota_topic(&root));
     let mut client = EspMqttClient::new(
         broker_url, 
         &mqtt_config,
         move |message_event| match message_event {
-            Ok(Received(msg)) => process_message(msg, &mut led),
+            Ok(Received(msg)) => {
+                process_message(
+                    msg,
+                    &player,
+                    codec,
+                    &action_tx,
+                    &opener,
+                    &mut verifier,
+                    &callback_settings,
+                    &scheme,
+                    callback_ota_topic.as_deref(),
+                )
+            }
             Ok(Published(msg_id)) => (),
             _ => warn!("Received from MQTT: {:?}", message_event),
         }
@@ -97,63 +282,434 @@ fn main() -> anyhow::Result<()> {
 
     info!(">>> EspMqttClient instantiated <<<");
 
-    // 2. publish an empty hello message
-    let publish_topic = hello_topic(UUID);
-    let empty_payload: &[u8] = &[];
+    // 2. publish a hello message, telling hosts what we can do
+    let capabilities = Capabilities {
+        firmware_version: env!("CARGO_PKG_VERSION").to_owned(),
+        board_revision: board_revision(),
+        codec,
+        commands: CommandKind::ALL.to_vec(),
+        sensors: vec![Sensor::ChipTemperature],
+        telemetry: vec![TelemetryKind::ChipTemperature],
+    };
+    let publish_topic = hello_topic(&root);
+    let hello_payload = sealed(&mut sealer, &publish_topic, capabilities.encode_with(codec))?;
+    // retained, so hosts that connect later learn about us too
     client.publish(
         publish_topic,
         QoS::AtLeastOnce,
-        false,
-        empty_payload,
+        true,
+        hello_payload,
     )?;
 
     info!(">>> Published hello topic <<<");
 
-    let empty_cmd = Command::BoardLed(RGB8::new(0,0,0));
-    client.subscribe(empty_cmd.topic(UUID), QoS::AtLeastOnce);
+    // retained, so hosts see our settings without asking
+    let current = *settings.lock().unwrap();
+    for key in ConfigKey::ALL {
+        publish_config(&mut client, &mut sealer, &root, codec, current.value(key))?;
+    }
+
+    let status_interval = Duration::from_secs(app_config.status_interval_s);
+    publish_status(&mut client, &mut sealer, &root, codec, heartbeat(boot, reset_reason))?;
+    let mut last_status = Instant::now();
+
+    // a batch of one sample is just an unbatched reading
+    let mut batcher = (app_config.batch_max_samples > 1).then(|| {
+        Batcher::new(app_config.batch_max_samples, app_config.batch_max_age_s * 1000)
+    });
+
+    // our own commands, broadcasts and those of the groups we start out in
+    let mut command_filters = vec![
+        scheme.cmd_filter(Target::Device(&uuid)),
+        scheme.cmd_filter(Target::All),
+    ];
+    for group in app_config.groups.split(',').map(str::trim).filter(|group| !group.is_empty()) {
+        if target::is_group_name(group) {
+            command_filters.push(scheme.cmd_filter(Target::Group(group)));
+        } else {
+            warn!("ignoring invalid group name {:?}", group);
+        }
+    }
+    for filter in command_filters {
+        client.subscribe(filter, QoS::AtLeastOnce)?;
+    }
+    if ota_enabled {
+        client.subscribe(ota_topic(&root), QoS::AtLeastOnce)?;
+    } else {
+        warn!("OTA updates are disabled, they need a command_key");
+    }
 
     info!(">>> Subscribed to all commands <<<");
 
+    if app_config.home_assistant {
+        if codec == Codec::Json {
+            // retained, so Home Assistant finds the board after its own restarts
+            for discovery in home_assistant::discoveries(&scheme, &uuid) {
+                client.publish(discovery.topic, QoS::AtLeastOnce, true, discovery.payload)?;
+            }
+            // the light entity sends `ColorData` here
+            client.subscribe(color_topic(&root), QoS::AtLeastOnce)?;
+            info!(">>> Published Home Assistant discovery <<<");
+        } else {
+            warn!("Home Assistant discovery needs payload_codec = \"json\"");
+        }
+    }
+
     loop {
-        sleep(Duration::from_secs(1));
+        let current = *settings.lock().unwrap();
+        let interval = Duration::from_millis(current.publish_interval_ms as u64);
+        let telemetry_qos = qos(current.mqtt_qos);
+        handle_actions(
+            &mut client,
+            &mut sealer,
+            &mut config_nvs,
+            &scheme,
+            &root,
+            &action_rx,
+            codec,
+            interval,
+        )?;
         // temperature
-        let temp = temp_sensor.read_owning_peripherals();
-        let temperature_data = &temp.to_be_bytes() as &[u8];
+        let temp = Telemetry::ChipTemperature(temp_sensor.read_owning_peripherals());
         // 3. publish CPU temperature
-        client.publish(
-            temperature_data_topic(UUID),
-            QoS::AtLeastOnce,
-            false,
-            temperature_data,
-        )?;
+        match &mut batcher {
+            Some(batcher) => {
+                let timestamp_ms = boot.elapsed().as_millis() as u64;
+                if let Some(batch) = batcher.push(timestamp_ms, temp) {
+                    let topic = batch.topic(&root);
+                    let payload = sealed(&mut sealer, &topic, batch.encode_with(codec))?;
+                    client.publish(topic, telemetry_qos, false, payload)?;
+                }
+            }
+            None => {
+                let topic = temp.topic(&root);
+                let payload = sealed(&mut sealer, &topic, Ok(temp.encode_with(codec)))?;
+                client.publish(topic, telemetry_qos, false, payload)?;
+            }
+        }
+
+        if last_status.elapsed() >= status_interval {
+            publish_status(&mut client, &mut sealer, &root, codec, heartbeat(boot, reset_reason))?;
+            last_status = Instant::now();
+        }
     }
 }
 
-fn process_message(message: &EspMqttMessage, led: &mut WS2812RMT) {
+/// `cfg.toml` values, overridden by those set remotely before
+fn load_settings(config: &Config, nvs: &Nvs) -> anyhow::Result<Settings> {
+    let mut settings = Settings::default();
+    let defaults = [
+        (ConfigKey::PublishInterval, config.publish_interval_ms),
+        (ConfigKey::LedBrightness, config.led_brightness),
+        (ConfigKey::MqttQos, config.mqtt_qos),
+    ];
+    for (key, value) in defaults {
+        if settings.set(key, value).is_err() {
+            warn!("{} = {} is out of range {:?}", key.name(), value, key.range());
+        }
+    }
+    for key in ConfigKey::ALL {
+        if let Some(value) = nvs.get_u32(&nvs_key(key))? {
+            settings.set(key, value).ok();
+        }
+    }
+    Ok(settings)
+}
+
+/// Topic names are too long for NVS, the numeric codes never change
+fn nvs_key(key: ConfigKey) -> String {
+    format!("key{}", key as u8)
+}
+
+fn qos(level: u8) -> QoS {
+    match level {
+        0 => QoS::AtMostOnce,
+        1 => QoS::AtLeastOnce,
+        _ => QoS::ExactlyOnce,
+    }
+}
+
+/// Seals `payload` for `topic` if sealing is configured
+fn sealed(
+    sealer: &mut Option<Sealer>,
+    topic: &str,
+    payload: Result<Vec<u8>, ConvertError>,
+) -> anyhow::Result<Vec<u8>> {
+    let payload = payload.map_err(|e| anyhow::anyhow!("could not encode {}: {:?}", topic, e))?;
+    match sealer {
+        Some(sealer) => sealer
+            .seal(topic, &payload)
+            .map_err(|e| anyhow::anyhow!("could not seal {}: {:?}", topic, e)),
+        None => Ok(payload),
+    }
+}
+
+/// Publishes a retained `Status::Online`, replacing the Last Will on the broker
+fn publish_status(
+    client: &mut EspMqttClient,
+    sealer: &mut Option<Sealer>,
+    root: &str,
+    codec: Codec,
+    heartbeat: Heartbeat,
+) -> anyhow::Result<()> {
+    let status = Status::Online(heartbeat);
+    let topic = status.topic(root);
+    let payload = sealed(sealer, &topic, status.encode_with(codec))?;
+    client.publish(topic, QoS::AtLeastOnce, true, payload)?;
+    Ok(())
+}
+
+fn heartbeat(boot: Instant, reset_reason: ResetReason) -> Heartbeat {
+    Heartbeat {
+        uptime_s: boot.elapsed().as_secs() as u32,
+        firmware_version: env!("CARGO_PKG_VERSION").to_owned(),
+        rssi: wifi_rssi(),
+        free_heap: unsafe { esp_idf_sys::esp_get_free_heap_size() },
+        reset_reason,
+    }
+}
+
+fn board_revision() -> String {
+    let mut chip_info = esp_idf_sys::esp_chip_info_t::default();
+    unsafe { esp_idf_sys::esp_chip_info(&mut chip_info) };
+    format!("ESP32-C3-DevKitC-02, chip revision {}", chip_info.revision)
+}
+
+/// The MAC burnt into the eFuses, which the interfaces' MACs derive from
+fn base_mac() -> anyhow::Result<[u8; 6]> {
+    let mut mac = [0; 6];
+    esp_idf_sys::esp!(unsafe { esp_idf_sys::esp_efuse_mac_get_default(mac.as_mut_ptr()) })?;
+    Ok(mac)
+}
+
+/// Signal strength of the access point we are connected to
+fn wifi_rssi() -> Option<i8> {
+    let mut ap_info = esp_idf_sys::wifi_ap_record_t::default();
+    esp_idf_sys::esp!(unsafe { esp_idf_sys::esp_wifi_sta_get_ap_info(&mut ap_info) }).ok()?;
+    Some(ap_info.rssi)
+}
+
+/// Publishes a retained `ConfigValue`
+fn publish_config(
+    client: &mut EspMqttClient,
+    sealer: &mut Option<Sealer>,
+    root: &str,
+    codec: Codec,
+    value: ConfigValue,
+) -> anyhow::Result<()> {
+    let topic = value.topic(root);
+    let payload = sealed(sealer, &topic, Ok(value.encode_with(codec)))?;
+    client.publish(topic, QoS::AtLeastOnce, true, payload)?;
+    Ok(())
+}
+
+/// Publishes acknowledgements and settings, persists settings and changes
+/// group subscriptions as they come in, until `duration` has passed
+fn handle_actions(
+    client: &mut EspMqttClient,
+    sealer: &mut Option<Sealer>,
+    config_nvs: &mut Nvs,
+    scheme: &TopicScheme,
+    root: &str,
+    actions: &Receiver<Action>,
+    codec: Codec,
+    duration: Duration,
+) -> anyhow::Result<()> {
+    let deadline = Instant::now() + duration;
+    while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+        match actions.recv_timeout(timeout) {
+            Ok(Action::Ack(ack)) => {
+                let topic = ack.topic(root);
+                let payload = sealed(sealer, &topic, ack.encode_with(codec))?;
+                client.publish(topic, QoS::AtLeastOnce, false, payload)?;
+            }
+            Ok(Action::JoinGroup(group)) => {
+                client.subscribe(scheme.cmd_filter(Target::Group(&group)), QoS::AtLeastOnce)?;
+                info!("Joined group {}", group);
+            }
+            Ok(Action::LeaveGroup(group)) => {
+                client.unsubscribe(scheme.cmd_filter(Target::Group(&group)))?;
+                info!("Left group {}", group);
+            }
+            Ok(Action::Config { value, changed }) => {
+                if changed {
+                    config_nvs.set_u32(&nvs_key(value.key), value.value)?;
+                }
+                publish_config(client, sealer, root, codec, value)?;
+            }
+            Ok(Action::Ota(report)) => {
+                let topic = report.topic(root);
+                let payload = sealed(sealer, &topic, report.encode_with(codec))?;
+                client.publish(topic, QoS::AtLeastOnce, false, payload)?;
+                if let OtaReport::Done { version } = report {
+                    // give the report a moment to leave before we go
+                    info!("Restarting into firmware {}", version);
+                    thread::sleep(Duration::from_secs(1));
+                    unsafe { esp_idf_sys::esp_restart() };
+                }
+            }
+            Err(_) => break,
+        }
+    }
+    Ok(())
+}
+
+/// Group changes only last until the next reboot, `groups` in `cfg.toml` is
+/// where a board starts out. Settings are persisted by the main loop.
+fn apply_command(
+    command: Command,
+    player: &Mutex<Player>,
+    actions: &Sender<Action>,
+    settings: &Mutex<Settings>,
+) -> (AckStatus, String) {
+    match command {
+        Command::BoardLed(color) => {
+            let brightness = settings.lock().unwrap().led_brightness;
+            match player.lock().unwrap().set_color(color, brightness) {
+                Ok(()) => {
+                    info!("Setting LED to {:?}", color);
+                    (AckStatus::Ok, String::new())
+                }
+                Err(e) => {
+                    error!("could not set board LED: {:?}", e);
+                    (AckStatus::Failed, format!("{:?}", e))
+                }
+            }
+        }
+        // the main loop handles these before the acknowledgement, so the
+        // subscription is in place once the host hears back
+        Command::JoinGroup(group) => {
+            actions.send(Action::JoinGroup(group)).ok();
+            (AckStatus::Ok, String::new())
+        }
+        Command::LeaveGroup(group) => {
+            actions.send(Action::LeaveGroup(group)).ok();
+            (AckStatus::Ok, String::new())
+        }
+        Command::ConfigGet { key } => {
+            let value = settings.lock().unwrap().value(key);
+            actions.send(Action::Config { value, changed: false }).ok();
+            (AckStatus::Ok, String::new())
+        }
+        // a rejected value is answered with the current one
+        Command::ConfigSet { key, value } => {
+            let mut settings = settings.lock().unwrap();
+            let result = settings.set(key, value);
+            let changed = result.is_ok();
+            actions.send(Action::Config { value: settings.value(key), changed }).ok();
+            match result {
+                Ok(()) => {
+                    info!("Setting {} to {}", key.name(), value);
+                    (AckStatus::Ok, String::new())
+                }
+                Err(_) => (
+                    AckStatus::Rejected,
+                    format!("{} must be within {:?}", key.name(), key.range()),
+                ),
+            }
+        }
+        // plain colors always stop the animation, priorities only matter
+        // between animations
+        Command::Animation(animation) => {
+            let priority = animation.priority;
+            if player.lock().unwrap().play(animation) {
+                info!("Playing animation with priority {}", priority);
+                (AckStatus::Ok, String::new())
+            } else {
+                (
+                    AckStatus::Rejected,
+                    "an animation with a higher priority is running".to_owned(),
+                )
+            }
+        }
+    }
+}
+
+/// Downloads and installs the firmware in the background, the main loop
+/// publishes the reports
+fn start_update(start: OtaStart, actions: &Sender<Action>) {
+    info!("Updating to firmware {} from {}", start.version, start.url);
+    let reports = actions.clone();
+    let spawned = updater::spawn(start, move |report| {
+        reports.send(Action::Ota(report)).ok();
+    });
+    if let Err(e) = spawned {
+        let reason = format!("{}", e);
+        actions.send(Action::Ota(OtaReport::Failed { reason })).ok();
+    }
+}
+
+fn process_message(
+    message: &EspMqttMessage,
+    player: &Mutex<Player>,
+    codec: Codec,
+    actions: &Sender<Action>,
+    opener: &Option<Opener>,
+    verifier: &mut Option<CommandVerifier>,
+    settings: &Mutex<Settings>,
+    scheme: &TopicScheme,
+    ota_topic: Option<&str>,
+) {
     match message.details() {
         // all messages in this exercise will be of type `Complete`
         // the other variants of the `Details` enum are for larger message payloads
         Complete => {
-            // Cow<&[u8]> can be coerced into a slice &[u8] or a Vec<u8>
-            // You can coerce it into a slice to be sent to try_from()
-            // RGB LED command
-            let is_command_topic = message.topic().unwrap().split("/").nth(1) == Some("command");
-            if is_command_topic {
-                let raw = RawCommandData {
-                    path: "",
-                    data: message.data(),
-                };
-                if let Ok(Command::BoardLed(color)) = Command::try_from(raw) {
-                    // set the LED to the newly received color
-                    led.set_pixel(color);
-                    info!("Setting LED to {:?}", color);
+            let topic = message.topic().unwrap();
+            let data = message.data();
+            let opened;
+            let data: &[u8] = match opener {
+                Some(opener) => match opener.open(&topic, &data) {
+                    Ok(payload) => {
+                        opened = payload;
+                        &opened
+                    }
+                    Err(e) => {
+                        warn!("could not open message on {}: {:?}", topic, e);
+                        return;
+                    }
+                },
+                None => &data,
+            };
+            // every subscription of ours drives the LED, so all of them need a signature
+            let data: &[u8] = match verifier {
+                Some(verifier) => match verifier.verify(&topic, &data) {
+                    Ok(payload) => payload,
+                    Err(e) => {
+                        warn!("rejected message on {}: {:?}", topic, e);
+                        return;
+                    }
+                },
+                None => &data,
+            };
+            // only set while commands are verified
+            if ota_topic == Some(&*topic) {
+                match OtaStart::decode_with(codec, data) {
+                    Ok(start) => start_update(start, actions),
+                    Err(e) => warn!("could not decode OTA request: {:?}", e),
+                }
+            } else if let Some((_, kind)) = scheme.parse_cmd_topic(&topic) {
+                // addressed to us, to everyone or to one of our groups
+                match CommandRequest::decode_kind_with(codec, kind, data) {
+                    Ok(request) => {
+                        let (status, detail) = apply_command(request.command, player, actions, settings);
+                        // only requests with a correlation ID get an answer
+                        if let Some(id) = request.id {
+                            actions.send(Action::Ack(Ack::new(id, status, detail))).ok();
+                        }
+                    }
+                    Err(e) => warn!("could not decode command: {:?}", e),
                 }
             } else {
-                let message_data: &[u8] = &message.data();
-                if let Ok(ColorData::BoardLed(color)) = ColorData::try_from(message_data) {
+                // Cow<&[u8]> can be coerced into a slice &[u8] or a Vec<u8>
+                // You can coerce it into a slice to be sent to try_from()
+                // RGB LED command
+                if let Ok(ColorData::BoardLed(color)) = ColorData::decode_with(codec, data) {
                     // set the LED to the newly received color
-                    led.set_pixel(color);
-                    info!("Setting LED to {:?}", color);
+                    let brightness = settings.lock().unwrap().led_brightness;
+                    match player.lock().unwrap().set_color(color, brightness) {
+                        Ok(()) => info!("Setting LED to {:?}", color),
+                        Err(e) => error!("could not set board LED: {:?}", e),
+                    }
                 }
             }
         },
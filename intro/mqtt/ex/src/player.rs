@@ -0,0 +1,72 @@
+// Plays `Animation`s on the board LED.
+// The MQTT callback starts them, a thread of their own renders the frames.
+
+use std::time::{Duration, Instant};
+
+use bsc::led::{RGB8, WS2812RMT};
+use esp32_c3_dkc02_bsc as bsc;
+use mqtt_messages::Animation;
+
+/// Time between two frames of an animation
+pub const FRAME: Duration = Duration::from_millis(20);
+
+pub struct Player {
+    led: WS2812RMT,
+    /// shown whenever no animation runs
+    color: RGB8,
+    /// `led_brightness` of the last frame
+    brightness: u8,
+    animation: Option<(Animation, Instant)>,
+}
+
+impl Player {
+    pub fn new(led: WS2812RMT) -> Self {
+        Self {
+            led,
+            color: RGB8::default(),
+            brightness: 0,
+            animation: None,
+        }
+    }
+
+    /// Stops any animation and shows `color`
+    pub fn set_color(&mut self, color: RGB8, brightness: u8) -> anyhow::Result<()> {
+        self.animation = None;
+        self.color = color;
+        self.brightness = brightness;
+        self.led.set_pixel(dimmed(color, brightness))
+    }
+
+    /// Starts `animation`, unless one with a higher priority is running
+    pub fn play(&mut self, animation: Animation) -> bool {
+        if let Some((running, _)) = &self.animation {
+            if running.priority > animation.priority {
+                return false;
+            }
+        }
+        self.animation = Some((animation, Instant::now()));
+        true
+    }
+
+    /// Shows the current frame, or the color again once the animation is over
+    /// or `brightness` changed
+    pub fn render(&mut self, brightness: u8) -> anyhow::Result<()> {
+        let color = match &self.animation {
+            Some((animation, start)) => animation.color_at(start.elapsed().as_millis() as u64),
+            None if brightness != self.brightness => None,
+            None => return Ok(()),
+        };
+        let color = color.unwrap_or_else(|| {
+            self.animation = None;
+            self.color
+        });
+        self.brightness = brightness;
+        self.led.set_pixel(dimmed(color, brightness))
+    }
+}
+
+/// Scales `color` by the `led_brightness` setting
+pub fn dimmed(color: RGB8, brightness: u8) -> RGB8 {
+    let scale = |channel: u8| (channel as u16 * brightness as u16 / 255) as u8;
+    RGB8::new(scale(color.r), scale(color.g), scale(color.b))
+}
@@ -0,0 +1,141 @@
+// Firmware updates: download an image over HTTPS, write it to the inactive
+// app partition while hashing it, and boot from it if the digest matches.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+use anyhow::{anyhow, bail};
+use embedded_svc::{
+    http::{
+        client::{Client, Request, RequestWrite, Response},
+        Headers, Status,
+    },
+    io::Read,
+};
+use esp_idf_svc::http::client::{EspHttpClient, EspHttpClientConfiguration};
+use esp_idf_sys::{self as sys, esp};
+use log::{info, warn};
+use mqtt_messages::{OtaReport, OtaStart};
+use sha2::{Digest, Sha256};
+
+/// Bytes written between two `OtaReport::Progress`
+const PROGRESS_STEP: u32 = 64 * 1024;
+
+/// TLS needs a lot more stack than the default
+const STACK_SIZE: usize = 10 * 1024;
+
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Runs the update in a thread of its own, passing every report to `report`
+///
+/// Fails if an update is already running. After `OtaReport::Done` the new
+/// firmware boots on the next restart.
+pub fn spawn(
+    start: OtaStart,
+    report: impl Fn(OtaReport) + Send + 'static,
+) -> anyhow::Result<()> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        bail!("an update is already running");
+    }
+    let spawned = thread::Builder::new().stack_size(STACK_SIZE).spawn(move || {
+        let result = update(&start, |received, total| {
+            report(OtaReport::Progress { received, total })
+        });
+        match result {
+            Ok(()) => {
+                info!("Firmware {} is ready", start.version);
+                report(OtaReport::Done { version: start.version });
+            }
+            Err(e) => {
+                warn!("update to {} failed: {:?}", start.version, e);
+                RUNNING.store(false, Ordering::SeqCst);
+                report(OtaReport::Failed { reason: format!("{}", e) });
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        RUNNING.store(false, Ordering::SeqCst);
+        return Err(e.into());
+    }
+    Ok(())
+}
+
+fn update(start: &OtaStart, mut progress: impl FnMut(u32, u32)) -> anyhow::Result<()> {
+    if !start.url.starts_with("https://") {
+        bail!("only https:// URLs are allowed");
+    }
+
+    let mut client = EspHttpClient::new(&EspHttpClientConfiguration {
+        use_global_ca_store: true,
+        crt_bundle_attach: Some(sys::esp_crt_bundle_attach),
+
+        ..Default::default()
+    })?;
+    let mut response = client.get(&start.url)?.into_writer(0)?.submit()?;
+    let status = response.status();
+    if !(200..=299).contains(&status) {
+        bail!("unexpected response code: {}", status);
+    }
+    let total = response.content_len().unwrap_or(0) as u32;
+
+    let partition = unsafe { sys::esp_ota_get_next_update_partition(std::ptr::null()) };
+    if partition.is_null() {
+        bail!("no partition to update, is the OTA partition table flashed?");
+    }
+    let mut handle: sys::esp_ota_handle_t = 0;
+    esp!(unsafe { sys::esp_ota_begin(partition, sys::OTA_SIZE_UNKNOWN as _, &mut handle) })?;
+
+    // from here on, failing has to release the partition again
+    let written = write_image(&mut response.reader(), handle, total, &mut progress);
+    let digest = match written {
+        Ok(digest) => digest,
+        Err(e) => {
+            unsafe { sys::esp_ota_abort(handle) };
+            return Err(e);
+        }
+    };
+    if digest[..] != start.sha256[..] {
+        unsafe { sys::esp_ota_abort(handle) };
+        bail!("SHA-256 of the image does not match");
+    }
+    // also checks that the image is a valid app
+    esp!(unsafe { sys::esp_ota_end(handle) })?;
+    esp!(unsafe { sys::esp_ota_set_boot_partition(partition) })?;
+    Ok(())
+}
+
+/// Copies the image to the partition, returning its SHA-256
+fn write_image(
+    reader: &mut impl Read,
+    handle: sys::esp_ota_handle_t,
+    total: u32,
+    progress: &mut impl FnMut(u32, u32),
+) -> anyhow::Result<[u8; 32]> {
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 1024];
+    let mut received = 0;
+    let mut reported = 0;
+    loop {
+        let size = reader
+            .read(&mut buf)
+            .map_err(|e| anyhow!("download failed: {:?}", e))?;
+        if size == 0 {
+            break;
+        }
+        let chunk = &buf[..size];
+        hasher.update(chunk);
+        esp!(unsafe { sys::esp_ota_write(handle, chunk.as_ptr() as *const _, size as _) })?;
+        received += size as u32;
+        if received - reported >= PROGRESS_STEP {
+            progress(received, total);
+            reported = received;
+        }
+    }
+    if total != 0 && received != total {
+        bail!("received {} of {} bytes", received, total);
+    }
+    progress(received, total);
+    Ok(hasher.finalize().into())
+}
@@ -1,11 +1,30 @@
-use mqtt_messages::{hello_topic, temperature_data_topic, ColorData, RGB8, Command};
+use get_uuid::registry::Registry;
+use mqtt_messages::{
+    ack::response_filter,
+    batch::{batch_topic, is_batch_topic},
+    config::config_filter,
+    hello_topic,
+    ota::{ota_status_topic, parse_digest},
+    sealing::{self, Opener, Party, Sealer},
+    signing::Signer,
+    status_topic,
+    topic_filter::{FLEET_HELLO, FLEET_SENSOR_DATA, FLEET_STATUS},
+    Ack, Capabilities, Codec, Command, CommandKind, CommandRequest, ConfigKey, ConfigValue,
+    ConvertError, OtaReport, OtaStart, Status, Target, Telemetry, TelemetryBatch, TelemetryKind,
+    TopicFilter, TopicScheme, RGB8,
+};
 use rand::Rng;
-use rumqttc::{Client, MqttOptions, Packet, QoS};
+use rumqttc::{Client, Connection, Event, MqttOptions, Packet, QoS};
+use std::collections::HashMap;
+use std::env;
 use std::error::Error;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
-const UUID: &'static str = get_uuid::uuid();
+/// How long to wait for the board to acknowledge a command
+const ACK_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(Debug)]
 #[toml_cfg::toml_config]
@@ -16,24 +35,103 @@ pub struct Config {
     mqtt_user: &'static str,
     #[default("")]
     mqtt_pass: &'static str,
+    #[default("binary")]
+    payload_codec: &'static str,
+    #[default(false)]
+    fleet: bool,
+    #[default("")]
+    command_key: &'static str,
+    #[default("")]
+    sealing_secret: &'static str,
+    #[default("")]
+    command_target: &'static str,
+    #[default("")]
+    topic_prefix: &'static str,
+    #[default("")]
+    board_uuid: &'static str,
+    #[default("")]
+    registry: &'static str,
 }
 
+/// The board we talk to, see `resolve_board`
+static BOARD_UUID: OnceLock<String> = OnceLock::new();
+
 fn main() -> Result<(), Box<dyn Error>> {
-    dbg!(CONFIG);
-    let client_id = UUID;
-    dbg!(UUID);
+    let mut registry = if CONFIG.registry.is_empty() {
+        None
+    } else {
+        Some(Registry::load(CONFIG.registry).map_err(|e| format!("{:#}", e))?)
+    };
+    let board = resolve_board(CONFIG.board_uuid, registry.as_ref())?;
+    BOARD_UUID.set(board).expect("resolved once");
+    let client_id = get_uuid::uuid();
+    dbg!(board_uuid());
     let mut mqttoptions = MqttOptions::new(client_id, CONFIG.mqtt_host, 1883);
     mqttoptions.set_credentials(CONFIG.mqtt_user, CONFIG.mqtt_pass);
 
     mqttoptions.set_keep_alive(Duration::from_secs(5));
 
+    let codec = Codec::from_name(CONFIG.payload_codec)
+        .ok_or_else(|| format!("unknown payload codec {:?}", CONFIG.payload_codec))?;
+    let target = command_target(CONFIG.command_target)
+        .ok_or_else(|| format!("invalid command target {:?}", CONFIG.command_target))?;
+    let scheme = TopicScheme::new(CONFIG.topic_prefix)
+        .map_err(|_| format!("invalid topic prefix {:?}", CONFIG.topic_prefix))?;
+    let root = scheme.root(board_uuid());
+
     let (mut client, mut connection) = Client::new(mqttoptions, 10);
 
-    client.subscribe(temperature_data_topic(UUID), QoS::AtMostOnce)?;
-    client.subscribe(hello_topic(UUID), QoS::AtMostOnce)?;
+    // `host-client ota <url> <sha256> <version>` updates our board instead
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.first().map(String::as_str) == Some("ota") {
+        let start = ota_start(&args[1..])?;
+        return update_firmware(client, connection, &scheme, codec, start);
+    }
+
+    let sensor_data = scheme.filter(FLEET_SENSOR_DATA);
+    let sensor_data = TopicFilter::new(&sensor_data)?;
+    let hello = scheme.filter(FLEET_HELLO);
+    let hello = TopicFilter::new(&hello)?;
+    let status = scheme.filter(FLEET_STATUS);
+    let status = TopicFilter::new(&status)?;
+    let responses = response_filter(&root);
+    let responses = TopicFilter::new(&responses)?;
+
+    if CONFIG.fleet {
+        client.subscribe(sensor_data.as_str(), QoS::AtMostOnce)?;
+        client.subscribe(hello.as_str(), QoS::AtMostOnce)?;
+        client.subscribe(status.as_str(), QoS::AtMostOnce)?;
+    } else {
+        for kind in TelemetryKind::ALL {
+            client.subscribe(kind.topic(&root), QoS::AtMostOnce)?;
+        }
+        client.subscribe(hello_topic(&root), QoS::AtMostOnce)?;
+        client.subscribe(status_topic(&root), QoS::AtMostOnce)?;
+        client.subscribe(batch_topic(&root), QoS::AtMostOnce)?;
+    }
+    client.subscribe(responses.as_str(), QoS::AtLeastOnce)?;
+    client.subscribe(config_filter(&root), QoS::AtLeastOnce)?;
+
+    // what each board told us in its hello message
+    let boards: Arc<Mutex<HashMap<String, Capabilities>>> = Default::default();
+
+    let (ack_tx, ack_rx) = mpsc::channel();
+    let known_boards = boards.clone();
     thread::spawn(move || {
         let mut rng = rand::thread_rng();
-        loop {
+        let (mut signer, mut sealer) = signer_and_sealer();
+        for id in 0.. {
+            // boards without capabilities run older firmware, which has the LED
+            let has_led = known_boards
+                .lock()
+                .unwrap()
+                .get(board_uuid())
+                .is_none_or(|board| board.supports(CommandKind::BoardLed));
+            if !has_led {
+                thread::sleep(Duration::from_secs(1));
+                continue;
+            }
+
             let dimmer = 20;
             let r: u8 = rng.gen::<u8>() / dimmer;
             let g: u8 = rng.gen::<u8>() / dimmer;
@@ -41,39 +139,332 @@ fn main() -> Result<(), Box<dyn Error>> {
             let color = RGB8::new(r, g, b);
             println!("setting new color: {}", color);
             // let color = ColorData::BoardLed(color);
-            let color = Command::BoardLed(color);
+            let command = Command::BoardLed(color);
+            let topic = command.topic_to(&scheme, target);
+            let request = CommandRequest::new(id, command);
+            let payload = protect(&mut signer, &mut sealer, &topic, request.encode_with(codec))
+                .expect("commands fit an envelope");
             client
-                .publish(
-                    color.topic(UUID),
-                    QoS::AtLeastOnce,
-                    false,
-                    color.data(),
-                )
+                .publish(topic, QoS::AtLeastOnce, false, payload)
                 .unwrap();
+            match await_ack(&ack_rx, id, ACK_TIMEOUT) {
+                Some(ack) if ack.detail.is_empty() => println!("command {}: {:?}", id, ack.status),
+                Some(ack) => println!("command {}: {:?} ({})", id, ack.status, ack.detail),
+                None => println!("command {}: no answer within {:?}", id, ACK_TIMEOUT),
+            }
             thread::sleep(Duration::from_secs(1));
         }
     });
 
     // Iterate to poll the eventloop for connection progress
-    for (_, notification) in connection.iter().enumerate() {
+    for notification in connection.iter() {
         // if you want to see *everything*, uncomment:
         // println!("Notification = {:#?}", notification);
 
         if let Ok(rumqttc::Event::Incoming(Packet::Publish(publish_data))) = notification {
-            if publish_data.topic == hello_topic(UUID) {
-                println!("board says hi!");
+            let topic = &publish_data.topic;
+            let payload = match open(&scheme, topic, &publish_data.payload) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    println!("could not open message on {}: {:?}", topic, e);
+                    continue;
+                }
+            };
+
+            if responses.matches(topic) {
+                match Ack::decode_with(codec, &payload) {
+                    Ok(ack) => {
+                        // the publisher thread only stops when the program does
+                        ack_tx.send(ack).ok();
+                    }
+                    Err(e) => println!("could not decode ack: {:?}", e),
+                }
+            }
+
+            if let Some(key) = ConfigKey::from_topic(&root, topic) {
+                match ConfigValue::decode_with(codec, &payload) {
+                    Ok(config) => println!("[{}] {} = {}", board_uuid(), key.name(), config.value),
+                    Err(e) => println!("could not decode {} value: {:?}", key.name(), e),
+                }
+            }
+
+            if let Some(uuid) = hello.uuid(topic) {
+                if let Some(registry) = &mut registry {
+                    remember(registry, uuid);
+                }
+                if payload.is_empty() {
+                    println!("board {} says hi!", uuid);
+                } else {
+                    match Capabilities::decode_with(codec, &payload) {
+                        Ok(capabilities) => {
+                            print_capabilities(uuid, &capabilities);
+                            boards.lock().unwrap().insert(uuid.to_owned(), capabilities);
+                        }
+                        Err(e) => println!("could not decode capabilities: {:?}", e),
+                    }
+                }
             }
 
-            if publish_data.topic == temperature_data_topic(UUID) {
-                let data: &[u8] = &publish_data.payload;
-                let data: Result<[u8; 4], _> = data.try_into();
+            if let Some(uuid) = status.uuid(topic) {
+                match Status::decode_with(codec, &payload) {
+                    Ok(Status::Online(heartbeat)) => println!(
+                        "[{}] online for {}s, firmware {}, RSSI {:?} dBm, {} bytes free, reset by {:?}",
+                        uuid,
+                        heartbeat.uptime_s,
+                        heartbeat.firmware_version,
+                        heartbeat.rssi,
+                        heartbeat.free_heap,
+                        heartbeat.reset_reason
+                    ),
+                    Ok(Status::Offline) => println!("[{}] offline", uuid),
+                    Err(e) => println!("could not decode status: {:?}", e),
+                }
+            }
 
-                if let Ok(data) = data {
-                    let temp: f32 = f32::from_be_bytes(data);
-                    println!("board temperature: {:.2}°C", temp)
+            if let Some(uuid) = sensor_data.uuid(topic) {
+                let board_root = scheme.root(uuid);
+                if is_batch_topic(&board_root, topic) {
+                    match TelemetryBatch::decode_with(codec, &payload) {
+                        Ok(batch) => {
+                            for sample in batch.samples {
+                                print!("{:>10} ms ", sample.timestamp_ms);
+                                print_telemetry(uuid, sample.telemetry);
+                            }
+                        }
+                        Err(e) => println!("could not decode batch: {:?}", e),
+                    }
+                } else if let Some(kind) = TelemetryKind::from_topic(&board_root, topic) {
+                    // only show what the board says it publishes
+                    let advertised = boards
+                        .lock()
+                        .unwrap()
+                        .get(uuid)
+                        .is_none_or(|board| board.publishes(kind));
+                    if !advertised {
+                        continue;
+                    }
+                    match Telemetry::decode_with(codec, kind, &payload) {
+                        Ok(telemetry) => print_telemetry(uuid, telemetry),
+                        Err(e) => println!("could not decode {:?} payload: {:?}", kind, e),
+                    }
                 }
             }
         }
     }
     Ok(())
 }
+
+fn board_uuid() -> &'static str {
+    BOARD_UUID.get().expect("resolved at startup")
+}
+
+/// `board` as a UUID, or looked up by alias or MAC in the registry
+///
+/// Without `board`, we talk to the board with our own UUID, as long as
+/// `uuid.toml` makes boards go by it too.
+fn resolve_board(board: &str, registry: Option<&Registry>) -> Result<String, Box<dyn Error>> {
+    if board.is_empty() {
+        // boards derive their UUID from their MAC unless told otherwise
+        return match get_uuid::override_uuid() {
+            Some(uuid) => Ok(uuid.to_string()),
+            None => Err("set `board_uuid` to the UUID the board logs at boot, \
+                 or `override = true` in get-uuid's uuid.toml"
+                .into()),
+        };
+    }
+    if get_uuid::Uuid::parse_str(board).is_ok() {
+        return Ok(board.to_owned());
+    }
+    let registry = registry.ok_or_else(|| {
+        format!(
+            "board {:?} is not a UUID, set `registry` to look it up",
+            board
+        )
+    })?;
+    match registry.find(board) {
+        Some(found) => Ok(found.uuid.to_string()),
+        None => Err(format!("no board {:?} in {}", board, CONFIG.registry).into()),
+    }
+}
+
+/// Adds a board that said hello to the registry
+fn remember(registry: &mut Registry, uuid: &str) {
+    let uuid = match get_uuid::Uuid::parse_str(uuid) {
+        Ok(uuid) => uuid,
+        Err(_) => return,
+    };
+    if registry.import(uuid) {
+        match registry.save(CONFIG.registry) {
+            Ok(()) => println!("added board {} to {}", uuid, CONFIG.registry),
+            Err(e) => println!("could not save {}: {:#}", CONFIG.registry, e),
+        }
+    }
+}
+
+/// `""` for the board in `board_uuid`, `"all"` or `"group/<name>"`
+fn command_target(target: &str) -> Option<Target<'_>> {
+    match target {
+        "" => Some(Target::Device(board_uuid())),
+        "all" => Some(Target::All),
+        _ => target
+            .strip_prefix("group/")
+            .filter(|name| mqtt_messages::target::is_group_name(name))
+            .map(Target::Group),
+    }
+}
+
+/// Signs and seals commands as configured
+fn signer_and_sealer() -> (Option<Signer<'static>>, Option<Sealer>) {
+    // milliseconds keep the nonces increasing across restarts of this client
+    let signer = (!CONFIG.command_key.is_empty())
+        .then(|| Signer::new(CONFIG.command_key.as_bytes(), unix_millis()));
+    // every run needs its own sealing session, two runs within the same
+    // millisecond (or second) would reuse nonces, so pick one at random
+    let sealer = (!CONFIG.sealing_secret.is_empty()).then(|| {
+        let key = sealing::device_key(CONFIG.sealing_secret.as_bytes(), board_uuid());
+        Sealer::new(&key, Party::Host, rand::random())
+    });
+    (signer, sealer)
+}
+
+/// Signs, then seals `payload`, whichever is configured
+fn protect(
+    signer: &mut Option<Signer>,
+    sealer: &mut Option<Sealer>,
+    topic: &str,
+    mut payload: Vec<u8>,
+) -> Result<Vec<u8>, ConvertError> {
+    if let Some(signer) = signer {
+        payload = signer.sign(topic, &payload)?;
+    }
+    if let Some(sealer) = sealer {
+        payload = sealer.seal(topic, &payload)?;
+    }
+    Ok(payload)
+}
+
+/// `<url> <sha256> <version>` of the `ota` subcommand
+fn ota_start(args: &[String]) -> Result<OtaStart, Box<dyn Error>> {
+    let [url, sha256, version] = args else {
+        return Err("usage: host-client ota <url> <sha256> <version>".into());
+    };
+    if !url.starts_with("https://") {
+        return Err(format!("{:?} is not an https:// URL", url).into());
+    }
+    let sha256 = parse_digest(sha256).map_err(|_| format!("{:?} is not a SHA-256", sha256))?;
+    Ok(OtaStart {
+        url: url.clone(),
+        sha256,
+        version: version.clone(),
+    })
+}
+
+/// Asks our board to update its firmware, printing its reports until it is done
+fn update_firmware(
+    mut client: Client,
+    mut connection: Connection,
+    scheme: &TopicScheme,
+    codec: Codec,
+    start: OtaStart,
+) -> Result<(), Box<dyn Error>> {
+    let root = scheme.root(board_uuid());
+    let reports = ota_status_topic(&root);
+    client.subscribe(&reports, QoS::AtLeastOnce)?;
+
+    let (mut signer, mut sealer) = signer_and_sealer();
+    let topic = start.topic(&root);
+    let payload = start
+        .encode_with(codec)
+        .and_then(|payload| protect(&mut signer, &mut sealer, &topic, payload))
+        .map_err(|e| format!("could not encode OTA request: {:?}", e))?;
+    client.publish(topic, QoS::AtLeastOnce, false, payload)?;
+    println!(
+        "asked {} to update to firmware {}",
+        board_uuid(),
+        start.version
+    );
+
+    for notification in connection.iter() {
+        let publish = match notification? {
+            Event::Incoming(Packet::Publish(publish)) if publish.topic == reports => publish,
+            _ => continue,
+        };
+        let payload = open(scheme, &publish.topic, &publish.payload)
+            .map_err(|e| format!("could not open OTA report: {:?}", e))?;
+        match OtaReport::decode_with(codec, &payload) {
+            Ok(OtaReport::Progress { received, total }) if total > 0 => println!(
+                "{} of {} bytes ({}%)",
+                received,
+                total,
+                received as u64 * 100 / total as u64
+            ),
+            Ok(OtaReport::Progress { received, .. }) => println!("{} bytes", received),
+            Ok(OtaReport::Done { version }) => {
+                println!("firmware {} installed, the board restarts", version);
+                return Ok(());
+            }
+            Ok(OtaReport::Failed { reason }) => {
+                return Err(format!("update failed: {}", reason).into())
+            }
+            Err(e) => println!("could not decode OTA report: {:?}", e),
+        }
+    }
+    Ok(())
+}
+
+/// Opens a sealed payload with the key of the board that published it
+fn open(scheme: &TopicScheme, topic: &str, payload: &[u8]) -> Result<Vec<u8>, ConvertError> {
+    if CONFIG.sealing_secret.is_empty() {
+        return Ok(payload.to_vec());
+    }
+    let uuid = scheme.uuid(topic).unwrap_or_default();
+    let key = sealing::device_key(CONFIG.sealing_secret.as_bytes(), uuid);
+    Opener::new(&key, Party::Board).open(topic, payload)
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is after 1970")
+        .as_millis() as u64
+}
+
+/// Waits for the `Ack` of request `id`, dropping answers to earlier requests
+fn await_ack(acks: &Receiver<Ack>, id: u32, timeout: Duration) -> Option<Ack> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let ack = acks
+            .recv_timeout(deadline.checked_duration_since(Instant::now())?)
+            .ok()?;
+        if ack.id == id {
+            return Some(ack);
+        }
+    }
+}
+
+fn print_capabilities(uuid: &str, capabilities: &Capabilities) {
+    println!(
+        "board {} says hi! firmware {} on {}",
+        uuid, capabilities.firmware_version, capabilities.board_revision
+    );
+    println!(
+        "[{}] {:?} payloads, commands {:?}, sensors {:?}, telemetry {:?}",
+        uuid,
+        capabilities.codec,
+        capabilities.commands,
+        capabilities.sensors,
+        capabilities.telemetry
+    );
+}
+
+fn print_telemetry(uuid: &str, telemetry: Telemetry) {
+    print!("[{}] ", uuid);
+    match telemetry {
+        Telemetry::ChipTemperature(temp) => println!("board temperature: {:.2}°C", temp),
+        Telemetry::AmbientTemperature(temp) => println!("ambient temperature: {:.2}°C", temp),
+        Telemetry::Humidity(humidity) => println!("humidity: {:.1}%", humidity),
+        Telemetry::Accel(a) => println!("accel: x {:.2} y {:.2} z {:.2} g", a.x, a.y, a.z),
+        Telemetry::Gyro(g) => println!("gyro: x {:.2} y {:.2} z {:.2} °/s", g.x, g.y, g.z),
+        Telemetry::StepCount(steps) => println!("steps: {}", steps),
+    }
+}